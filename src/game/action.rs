@@ -0,0 +1,58 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    replay::Replay,
+    state::{is_board_input_state, GameState},
+};
+
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerAction>();
+    }
+}
+
+/// Systems that send [`PlayerAction`]s. Anything reading them should run after this.
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SendPlayerActions;
+
+/// Everything the player can do to the board. Mouse and keyboard input is translated into these,
+/// and replays feed them back in, so both go through the same code paths.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum PlayerAction {
+    PickCard {
+        position: Vec3,
+        #[serde(default)]
+        mode: PickMode,
+    },
+    DropCard {
+        position: Option<Vec3>,
+    },
+    MoveCamera {
+        translation: Vec3,
+    },
+}
+
+/// What comes along when a card is picked up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PickMode {
+    /// The card and every card stacked on top of it. Picking the root moves the whole stack.
+    #[default]
+    Stack,
+    /// Only the card itself. The cards below and above it are linked back together.
+    Single,
+}
+
+/// Run criteria for systems reading the mouse or keyboard, which are ignored while the board
+/// isn't shown and while a replay is driving the game.
+pub fn live_input(replay: Option<Res<Replay>>, state: Res<State<GameState>>) -> ShouldRun {
+    if !is_board_input_state(*state.current()) {
+        return ShouldRun::No;
+    }
+    match replay {
+        Some(replay) if !replay.is_finished() => ShouldRun::No,
+        _ => ShouldRun::Yes,
+    }
+}
@@ -31,6 +31,12 @@ impl AnimateRange {
         self.timer.reset();
     }
 
+    /// Jumps to the end of the range, as if the animation had already played.
+    pub fn finish(&mut self) {
+        self.set_percent(1.0);
+        self.timer.tick(Duration::ZERO);
+    }
+
     pub fn just_finished(&mut self) -> bool {
         self.timer.just_finished()
     }
@@ -2,7 +2,9 @@ use std::time::Duration;
 
 use bevy::{input::mouse::MouseWheel, prelude::*};
 
+use crate::game::action::{live_input, PlayerAction, SendPlayerActions};
 use crate::game::animate::{AnimateRange, Ease};
+use crate::game::replay::replay_actions;
 
 #[derive(Component)]
 pub struct PlayerCamera {
@@ -18,7 +20,17 @@ pub struct PlayerCameraPlugin;
 
 impl Plugin for PlayerCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup_camera).add_system(move_camera);
+        app.add_startup_system(setup_camera)
+            .add_system(
+                move_camera
+                    .with_run_criteria(live_input)
+                    .label(SendPlayerActions),
+            )
+            .add_system(
+                apply_camera_actions
+                    .after(move_camera)
+                    .after(replay_actions),
+            );
     }
 }
 
@@ -42,7 +54,8 @@ pub fn move_camera(
     time: Res<Time>,
     input: Res<Input<KeyCode>>,
     mut mouse_wheel_events: EventReader<MouseWheel>,
-    mut cameras: Query<(&PlayerCamera, &mut Transform)>,
+    mut actions: EventWriter<PlayerAction>,
+    cameras: Query<(&PlayerCamera, &Transform)>,
 ) {
     for event in mouse_wheel_events.iter() {
         match event.unit {
@@ -59,10 +72,10 @@ pub fn move_camera(
             *view_height -= 1;
         }
 
-        *view_height = view_height.min(1).max(-1);
+        *view_height = view_height.clamp(-1, 1);
     }
 
-    for (camera, mut transform) in &mut cameras {
+    for (camera, transform) in &cameras {
         let mut direction = Vec3::ZERO;
         if input.any_pressed([KeyCode::A, KeyCode::Left]) {
             direction.x -= 1.0;
@@ -80,15 +93,33 @@ pub fn move_camera(
         if direction.length() > 0.01 {
             direction = direction.normalize();
         }
-        transform.translation += direction * camera.base_speed * time.delta_seconds();
+        let mut translation =
+            transform.translation + direction * camera.base_speed * time.delta_seconds();
 
         let target_z = 8.0 + *view_height as f32 * 3.0;
         let mut animation = AnimateRange::new(
             Duration::from_secs_f32(0.2),
             Ease::Linear,
-            transform.translation.z..target_z,
+            translation.z..target_z,
             false,
         );
-        transform.translation.z = animation.tick(time.delta());
+        translation.z = animation.tick(time.delta());
+
+        if translation != transform.translation {
+            actions.send(PlayerAction::MoveCamera { translation });
+        }
+    }
+}
+
+pub fn apply_camera_actions(
+    mut actions: EventReader<PlayerAction>,
+    mut cameras: Query<&mut Transform, With<PlayerCamera>>,
+) {
+    for action in actions.iter() {
+        if let PlayerAction::MoveCamera { translation } = *action {
+            for mut transform in &mut cameras {
+                transform.translation = translation;
+            }
+        }
     }
 }
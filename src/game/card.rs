@@ -1,13 +1,30 @@
+use std::borrow::Cow;
 use std::time::Duration;
 
 use bevy::prelude::{shape::Quad, *};
 use bevy::utils::{Entry, HashMap, HashSet};
-use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::game::action::{live_input, PickMode, PlayerAction, SendPlayerActions};
 use crate::game::animate::{AnimateRange, Ease};
-use crate::game::camera::PlayerCamera;
-use crate::game::progress_bar::{ProgressBar, ProgressBarBundle};
-use crate::game::tile::{HoveredTile, Tile};
+use crate::game::camera::{apply_camera_actions, PlayerCamera};
+use crate::game::card_definition::{
+    update_card_registry, CardDefinitions, CardDefinitionsLoader, CardRegistry,
+};
+use crate::game::combat::{CombatState, Dying};
+use crate::game::config::GameConfig;
+use crate::game::events::{CardSlotted, CardSpawned, CardStacked, CardUnstacked};
+use crate::game::progress_bar::{
+    ProgressBar, ProgressBarBundle, ProgressBarState, ProgressBarStyle,
+};
+use crate::game::recipe::{
+    update_recipes, Recipe, RecipeDefinitions, RecipeId, Recipes, RecipesLoader,
+};
+use crate::game::save::RestoredCard;
+use crate::game::simulation::{SimulationClock, SimulationStage};
+use crate::game::spatial::{index_cards, SpatialIndex};
+use crate::game::state::{board_input, GameState};
+use crate::game::tile::{Tile, TileGrid};
 
 pub struct CardPlugin;
 
@@ -16,25 +33,56 @@ impl Plugin for CardPlugin {
         app.init_resource::<SelectedCard>()
             .init_resource::<HoverPoint>()
             .init_resource::<StackRoots>()
-            .init_resource::<CardData>()
-            .add_system_to_stage(CoreStage::PostUpdate, on_spawn_card)
-            .add_system(collide_cards)
+            .add_asset::<RecipeDefinitions>()
+            .init_asset_loader::<RecipesLoader>()
+            .init_resource::<Recipes>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_recipes)
+            .add_asset::<CardDefinitions>()
+            .init_asset_loader::<CardDefinitionsLoader>()
+            .init_resource::<CardRegistry>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_card_registry)
+            .add_system_to_stage(SimulationStage, init_cards.before(index_cards))
+            .add_system_to_stage(CoreStage::PostUpdate, init_cards)
             .add_system(
                 select_card
-                    .after(crate::game::camera::move_camera)
-                    .after(collide_cards),
+                    .with_run_criteria(board_input)
+                    .after(SendPlayerActions),
             )
-            .add_system(move_cards.after(select_card))
-            .add_system(evaluate_stacks.after(move_cards))
-            .add_system(handle_enemies.after(evaluate_stacks))
-            .add_system(combat.after(handle_enemies))
-            .add_system(set_hearts.after(combat));
+            .add_system(move_cards.with_run_criteria(board_input).after(select_card))
+            .add_system_to_stage(SimulationStage, evaluate_stacks);
+    }
+}
+
+pub struct CardPresentationPlugin;
+
+impl Plugin for CardPresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CardData>()
+            .add_system_to_stage(CoreStage::PostUpdate, update_card_materials)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                on_spawn_card.after(init_cards).after(update_card_materials),
+            )
+            .add_startup_system(spawn_drop_preview)
+            .add_system(
+                update_hover_point
+                    .with_run_criteria(live_input)
+                    .after(apply_camera_actions),
+            )
+            .add_system(
+                mouse_actions
+                    .with_run_criteria(live_input)
+                    .label(SendPlayerActions)
+                    .after(update_hover_point),
+            )
+            .add_system(update_drop_preview.after(move_cards))
+            .add_system(animate_cards.after(move_cards))
+            .add_system(set_hearts);
     }
 }
 
 #[derive(Component, Default)]
 pub struct Card {
-    pub animations: Animations,
     pub info: CardInfo,
     pub z: usize,
     pub combat_state: Option<CombatState>,
@@ -43,11 +91,6 @@ pub struct Card {
     pub slotted_in_tile: Option<Entity>,
 }
 
-pub struct CombatState {
-    cooldown: Timer,
-    target: Entity,
-}
-
 impl From<CardType> for Card {
     fn from(card_type: CardType) -> Self {
         Self {
@@ -62,14 +105,18 @@ impl Card {
     pub const ART_WIDTH: f32 = 167.0;
     pub const ART_HEIGHT: f32 = 166.0;
     pub const ART_ASPECT: f32 = Self::ART_WIDTH / Self::ART_HEIGHT;
+    pub const SIZE: Vec2 = Vec2::from_array([Self::ASPECT_RATIO, 1.0]);
     pub const SPAWN_OFFSET: f32 = 1.0;
+    /// How far each card in a stack sits from the one below it.
+    pub const STACK_OFFSET: Vec3 = Vec3::new(0.0, -0.3, 0.01);
+    pub const LUNGE_DISTANCE: f32 = 0.6;
 
-    pub fn card_type(&self) -> CardType {
-        self.info.card_type
+    pub fn card_type(&self) -> &CardType {
+        &self.info.card_type
     }
 
     pub fn class(&self) -> CardClass {
-        self.info.card_type.class()
+        self.info.class
     }
 
     pub fn is_stackable(&self) -> bool {
@@ -89,71 +136,61 @@ impl Card {
     }
 }
 
-#[derive(Default, Copy, Clone, Hash, PartialEq, Eq, Debug)]
-pub enum CardType {
-    #[default]
-    Villager,
-    Log,
-    Goblin,
-}
+/// The id of a [`CardDefinition`](crate::game::card_definition::CardDefinition).
+#[derive(Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardType(pub Cow<'static, str>);
 
-pub struct CardInfo {
-    pub card_type: CardType,
-    pub stats: CardStats,
+impl CardType {
+    pub const VILLAGER: CardType = CardType(Cow::Borrowed("villager"));
+    pub const LOG: CardType = CardType(Cow::Borrowed("log"));
+    pub const GOBLIN: CardType = CardType(Cow::Borrowed("goblin"));
+    pub const STONE: CardType = CardType(Cow::Borrowed("stone"));
+    pub const WHEAT: CardType = CardType(Cow::Borrowed("wheat"));
+    pub const BERRIES: CardType = CardType(Cow::Borrowed("berries"));
+    pub const FISH: CardType = CardType(Cow::Borrowed("fish"));
 }
 
-impl Default for CardInfo {
+impl Default for CardType {
     fn default() -> Self {
-        CardType::default().into()
+        Self::VILLAGER
     }
 }
 
-impl From<CardType> for CardInfo {
-    fn from(card_type: CardType) -> Self {
-        let stats = card_type.get_initial_stats();
-        Self { card_type, stats }
-    }
+/// Class and stats are filled in from the card's definition when the card is spawned.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct CardInfo {
+    pub card_type: CardType,
+    pub class: CardClass,
+    pub stats: CardStats,
 }
 
-impl CardType {
-    pub fn class(&self) -> CardClass {
-        match self {
-            CardType::Villager { .. } => CardClass::Villager,
-            CardType::Log => CardClass::Resource,
-            CardType::Goblin { .. } => CardClass::Enemy,
-        }
-    }
-
-    pub fn get_initial_stats(&self) -> CardStats {
-        match self {
-            CardType::Villager => CardStats {
-                health: 3,
-                max_health: 3,
-                damage: 1,
-            },
-            CardType::Goblin => CardStats {
-                health: 1,
-                max_health: 1,
-                damage: 1,
-            },
-            _ => CardStats {
-                health: 0,
-                max_health: 0,
-                damage: 0,
-            },
+impl From<CardType> for CardInfo {
+    fn from(card_type: CardType) -> Self {
+        Self {
+            card_type,
+            ..default()
         }
     }
 }
 
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct CardStats {
     pub health: isize,
     pub max_health: usize,
     pub damage: usize,
+    /// Attacks per second.
+    #[serde(default)]
+    pub attack_speed: f32,
+    /// How fast the card works a tile it's slotted in, relative to the tile's production time.
+    #[serde(default)]
+    pub work_speed: f32,
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Default, PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum CardClass {
     Villager,
+    #[default]
     Resource,
     Enemy,
 }
@@ -181,14 +218,10 @@ pub enum HoverPoint {
     None,
 }
 
-#[derive(Bundle)]
+#[derive(Bundle, Default)]
 pub struct CardBundle {
     pub card: Card,
-    pub collider: Collider,
-    pub sensor: Sensor,
-    pub rigid_body: RigidBody,
-    pub active_events: ActiveEvents,
-    pub active_collision_types: ActiveCollisionTypes,
+    pub animations: Animations,
     pub transform: Transform,
     pub global_transform: GlobalTransform,
     pub visibility: Visibility,
@@ -199,7 +232,10 @@ pub struct CardBundle {
 pub enum StackType {
     Pending,
     Nothing,
-    Breed { progress_bar: Entity },
+    Recipe {
+        recipe: RecipeId,
+        progress_bar: Entity,
+    },
 }
 
 #[derive(Default)]
@@ -208,20 +244,32 @@ pub struct StackRoots {
     queued_stack_recomputations: HashSet<Entity>,
 }
 
-impl Default for CardBundle {
-    fn default() -> Self {
-        Self {
-            collider: Collider::cuboid(Card::ASPECT_RATIO / 2.0, 1.0 / 2.0, 0.2),
-            sensor: Sensor,
-            active_events: ActiveEvents::COLLISION_EVENTS,
-            active_collision_types: ActiveCollisionTypes::all(),
-            rigid_body: RigidBody::Fixed,
-            card: Default::default(),
-            transform: Default::default(),
-            global_transform: Default::default(),
-            visibility: Default::default(),
-            computed_visibiltiy: Default::default(),
-        }
+impl StackRoots {
+    pub fn get(&self, root: Entity) -> Option<&StackType> {
+        self.roots.get(&root)
+    }
+
+    pub fn insert(&mut self, root: Entity, stack_type: StackType) {
+        self.roots.insert(root, stack_type);
+    }
+
+    pub fn remove(&mut self, root: Entity) -> Option<StackType> {
+        self.roots.remove(&root)
+    }
+
+    pub fn queue_recomputation(&mut self, entity: Entity) {
+        self.queued_stack_recomputations.insert(entity);
+    }
+
+    /// Has every stack's recipe looked up again, e.g. after the recipes changed.
+    pub fn queue_all(&mut self) {
+        self.queued_stack_recomputations
+            .extend(self.roots.keys().copied());
+    }
+
+    pub fn clear(&mut self) {
+        self.roots.clear();
+        self.queued_stack_recomputations.clear();
     }
 }
 
@@ -232,11 +280,10 @@ pub struct CardData {
     villager_base: Handle<StandardMaterial>,
     resource_base: Handle<StandardMaterial>,
     enemy_base: Handle<StandardMaterial>,
-    villager_portrait_base: Handle<StandardMaterial>,
-    log_portrait_base: Handle<StandardMaterial>,
-    goblin_portrait_base: Handle<StandardMaterial>,
+    portraits: HashMap<CardType, Handle<StandardMaterial>>,
     heart_material: Handle<StandardMaterial>,
     removed_heart_material: Handle<StandardMaterial>,
+    hit_material: Handle<StandardMaterial>,
 }
 
 impl FromWorld for CardData {
@@ -285,18 +332,7 @@ impl FromWorld for CardData {
                 }
                 .into(),
             ),
-            villager_portrait_base: materials.add(StandardMaterial {
-                base_color_texture: Some(asset_server.load("villager.png")),
-                ..villager_base.clone()
-            }),
-            log_portrait_base: materials.add(StandardMaterial {
-                base_color_texture: Some(asset_server.load("log.png")),
-                ..resource_base.clone()
-            }),
-            goblin_portrait_base: materials.add(StandardMaterial {
-                base_color_texture: Some(asset_server.load("goblin.png")),
-                ..enemy_base.clone()
-            }),
+            portraits: HashMap::new(),
             heart_material: materials.add(StandardMaterial {
                 base_color: Color::rgba_u8(200, 90, 90, 255),
                 base_color_texture: Some(asset_server.load("heart.png")),
@@ -313,6 +349,10 @@ impl FromWorld for CardData {
                 depth_bias: 0.1,
                 ..default()
             }),
+            hit_material: materials.add(StandardMaterial {
+                base_color: Color::rgb(1.0, 0.9, 0.9),
+                ..card_base_material.clone()
+            }),
             villager_base: materials.add(villager_base),
             resource_base: materials.add(resource_base),
             enemy_base: materials.add(enemy_base),
@@ -328,11 +368,80 @@ impl CardData {
             CardClass::Enemy => self.enemy_base.clone(),
         }
     }
-    pub fn portrait_material(&self, card_type: CardType) -> Handle<StandardMaterial> {
-        match card_type {
-            CardType::Villager { .. } => self.villager_portrait_base.clone(),
-            CardType::Log => self.log_portrait_base.clone(),
-            CardType::Goblin { .. } => self.goblin_portrait_base.clone(),
+
+    /// The look of the `i`th heart of a card with `stats`, full or lost.
+    fn heart_material_for(&self, i: usize, stats: &CardStats) -> &Handle<StandardMaterial> {
+        if (i as isize) < stats.health {
+            &self.heart_material
+        } else {
+            &self.removed_heart_material
+        }
+    }
+
+    pub fn portrait_material(&self, card_type: &CardType) -> Handle<StandardMaterial> {
+        self.portraits.get(card_type).cloned().unwrap_or_default()
+    }
+}
+
+fn update_card_materials(
+    asset_server: Res<AssetServer>,
+    registry: Res<CardRegistry>,
+    mut card_data: ResMut<CardData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !registry.is_changed() {
+        return;
+    }
+    for definition in registry.iter() {
+        let portrait = StandardMaterial {
+            base_color: definition.tint,
+            base_color_texture: Some(asset_server.load(&definition.portrait)),
+            unlit: true,
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        };
+        // update existing materials in place so cards already on the board pick up the changes
+        match card_data.portraits.get(&definition.id) {
+            Some(handle) => {
+                if let Some(material) = materials.get_mut(handle) {
+                    *material = portrait;
+                }
+            }
+            None => {
+                let handle = materials.add(portrait);
+                card_data.portraits.insert(definition.id.clone(), handle);
+            }
+        }
+    }
+}
+
+/// Marks cards that [`init_cards`] has already seen.
+#[derive(Component)]
+pub struct InitializedCard;
+
+/// Fills in class and stats of new cards from their definitions and sends [`CardSpawned`]. Runs at
+/// the start of every simulation step, so cards spawned by the previous step are complete before
+/// anything else looks at them, and again after `Update` for cards spawned outside the simulation.
+fn init_cards(
+    mut commands: Commands,
+    registry: Res<CardRegistry>,
+    mut cards: Query<(Entity, &mut Card, Option<&RestoredCard>), Without<InitializedCard>>,
+    mut events: EventWriter<CardSpawned>,
+) {
+    for (entity, mut card, restored) in &mut cards {
+        commands.entity(entity).insert(InitializedCard);
+        events.send(CardSpawned {
+            card: entity,
+            card_type: card.card_type().clone(),
+        });
+        if restored.is_some() {
+            continue;
+        }
+        if let Some(definition) = registry.get(card.card_type()) {
+            card.info.class = definition.class;
+            card.info.stats = definition.stats();
+        } else {
+            warn!("no definition found for card type {:?}", card.card_type());
         }
     }
 }
@@ -347,89 +456,221 @@ fn on_spawn_card(
     cards: Query<(Entity, &Card), Added<Card>>,
 ) {
     for (entity, card) in &cards {
-        commands.entity(entity).with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
+        let background = commands
+            .spawn_bundle(PbrBundle {
                 material: card_data.class_material(card.class()),
                 mesh: card_data.mesh.clone(),
                 ..default()
-            });
-            parent.spawn_bundle(PbrBundle {
+            })
+            .insert(CardBackground)
+            .id();
+        let portrait = commands
+            .spawn_bundle(PbrBundle {
                 material: card_data.portrait_material(card.card_type()),
                 mesh: card_data.portrait_mesh.clone(),
                 transform: Transform::from_xyz(0.0, -0.08, 0.001),
                 ..default()
-            });
-            parent
-                .spawn_bundle(SpatialBundle::default())
-                .with_children(|parent| {
-                    let max = card.info.stats.max_health;
-                    let offset = HEART_PANEL_WIDTH / max as f32;
-                    let width = (max - 1) as f32 * offset;
-                    for i in 0..max {
-                        parent.spawn_bundle(PbrBundle {
-                            material: card_data.heart_material.clone(),
-                            mesh: card_data.heart_mesh.clone(),
-                            transform: Transform::from_xyz(
-                                i as f32 * offset - width / 2.0,
-                                0.37,
-                                0.01,
-                            ),
-                            ..default()
-                        });
-                    }
-                });
-        });
+            })
+            .insert(CardPortrait)
+            .id();
+        let heart_panel = commands
+            .spawn_bundle(SpatialBundle::default())
+            .insert(HeartPanel)
+            .with_children(|parent| {
+                spawn_hearts(parent, &card_data, &card.info.stats);
+            })
+            .id();
+        // attacks and deaths move the pivot, leaving the card's own transform to the simulation
+        let pivot = commands
+            .spawn_bundle(SpatialBundle::default())
+            .push_children(&[background, portrait, heart_panel])
+            .id();
+        commands
+            .entity(entity)
+            .insert(CardVisuals {
+                pivot,
+                background,
+                heart_panel,
+            })
+            .add_child(pivot);
     }
 }
 
+fn spawn_hearts(parent: &mut ChildBuilder, card_data: &CardData, stats: &CardStats) {
+    let max = stats.max_health;
+    if max == 0 {
+        return;
+    }
+    let offset = HEART_PANEL_WIDTH / max as f32;
+    let width = (max - 1) as f32 * offset;
+    for i in 0..max {
+        parent
+            .spawn_bundle(PbrBundle {
+                material: card_data.heart_material_for(i, stats).clone(),
+                mesh: card_data.heart_mesh.clone(),
+                transform: Transform::from_xyz(i as f32 * offset - width / 2.0, 0.37, 0.01),
+                ..default()
+            })
+            .insert(Heart(i));
+    }
+}
+
+/// The entities [`on_spawn_card`] builds a card's look from.
+#[derive(Component)]
+pub struct CardVisuals {
+    pivot: Entity,
+    background: Entity,
+    heart_panel: Entity,
+}
+
+/// The card-shaped quad behind the portrait, colored by the card's class.
+#[derive(Component)]
+pub struct CardBackground;
+
+/// The card's art, tinted and textured by its definition.
+#[derive(Component)]
+pub struct CardPortrait;
+
+/// Holds one [`Heart`] per point of the card's max health.
+#[derive(Component)]
+pub struct HeartPanel;
+
+/// The `i`th heart from the left of a [`HeartPanel`].
+#[derive(Component)]
+pub struct Heart(pub usize);
+
+/// Colors hearts by the card's health, and rebuilds the panel when its max health no longer
+/// matches the hearts it has. Only looks at cards that changed, which most resting cards haven't.
 fn set_hearts(
+    mut commands: Commands,
     card_data: Res<CardData>,
-    cards: Query<(&Card, &Children)>,
+    cards: Query<(&Card, &CardVisuals), Changed<Card>>,
     children: Query<&Children>,
-    mut materials: Query<&mut Handle<StandardMaterial>>,
+    mut hearts: Query<(&Heart, &mut Handle<StandardMaterial>)>,
 ) {
-    for (card, card_children) in &cards {
-        if card.info.stats.max_health > 0 {
-            let heart_children = children.get(card_children[2]).unwrap();
-            for i in 0..card.info.stats.max_health {
-                let child = heart_children[i];
-                let mut material = materials.get_mut(child).unwrap();
-                if i < card.info.stats.health as usize {
-                    *material = card_data.heart_material.clone();
-                } else {
-                    *material = card_data.removed_heart_material.clone();
+    for (card, visuals) in &cards {
+        let panel_hearts: Vec<Entity> = children
+            .get(visuals.heart_panel)
+            .map(|children| {
+                children
+                    .iter()
+                    .copied()
+                    .filter(|child| hearts.contains(*child))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if panel_hearts.len() != card.info.stats.max_health {
+            let mut heart_panel = commands.entity(visuals.heart_panel);
+            heart_panel.despawn_descendants();
+            heart_panel.with_children(|parent| {
+                spawn_hearts(parent, &card_data, &card.info.stats);
+            });
+            continue;
+        }
+        for child in panel_hearts {
+            if let Ok((Heart(i), mut material)) = hearts.get_mut(child) {
+                let target = card_data.heart_material_for(*i, &card.info.stats);
+                if *material != *target {
+                    *material = target.clone();
                 }
             }
         }
     }
 }
 
+fn animate_cards(
+    time: Res<Time>,
+    card_data: Res<CardData>,
+    mut cards: Query<(&Card, &mut Animations, &CardVisuals, Option<&Dying>)>,
+    mut transforms: Query<&mut Transform, Without<Card>>,
+    mut backgrounds: Query<&mut Handle<StandardMaterial>, With<CardBackground>>,
+) {
+    for (card, mut animations, visuals, dying) in &mut cards {
+        let class = card.class();
+        // lunge towards the target, then back
+        let lunge = if animations.attack_in.finished() {
+            animations.attack_out.tick(time.delta())
+        } else {
+            animations.attack_in.tick(time.delta())
+        };
+        let flashing = !animations.hit.tick(time.delta()).finished();
+
+        if let Ok(mut transform) = transforms.get_mut(visuals.pivot) {
+            let translation = animations.attack_direction * (lunge - 1.0) * Card::LUNGE_DISTANCE;
+            let scale = Vec3::splat(dying.map_or(1.0, |dying| 1.0 - dying.percent()));
+            // resting cards keep their pivot untouched, so it isn't propagated again every frame
+            if transform.translation != translation || transform.scale != scale {
+                transform.translation = translation;
+                transform.scale = scale;
+            }
+        }
+        if let Ok(mut material) = backgrounds.get_mut(visuals.background) {
+            let target = if flashing {
+                card_data.hit_material.clone()
+            } else {
+                card_data.class_material(class)
+            };
+            if *material != target {
+                *material = target;
+            }
+        }
+    }
+}
+
 fn move_cards(
     time: Res<Time>,
     selected: Res<SelectedCard>,
     hover_point: Res<HoverPoint>,
     stack_roots: Res<StackRoots>,
-    mut cards: Query<(Entity, &mut Card, &mut Transform)>,
-    mut transforms: Query<&Transform, Without<Card>>,
+    mut previously_selected: Local<SelectedCard>,
+    mut cards: Query<(Entity, &Card, &mut Transform)>,
+    mut animations: Query<&mut Animations>,
+    tiles: Query<(&Tile, &Transform), Without<Card>>,
 ) {
-    for (entity, mut card, mut transform) in &mut cards {
-        let mut z_offset = 0.0;
-        if selected.is_selected(entity) {
-            z_offset += card.animations.select.tick(time.delta());
+    if *selected != *previously_selected {
+        if let SelectedCard::Some(entity) = *previously_selected {
+            if let Ok(mut animations) = animations.get_mut(entity) {
+                animations.deselect.reset();
+            }
+        }
+        if let SelectedCard::Some(entity) = *selected {
+            if let Ok(mut animations) = animations.get_mut(entity) {
+                animations.select.reset();
+            }
+        }
+        *previously_selected = *selected;
+    }
+
+    for (entity, card, mut transform) in &mut cards {
+        let mut translation = transform.translation;
+        let mut card_animations = match animations.get_mut(entity) {
+            Ok(card_animations) => card_animations,
+            Err(_) => continue,
+        };
+        translation.z = if selected.is_selected(entity) {
             if let HoverPoint::Some(hover_point) = *hover_point {
-                transform.translation.x = hover_point.x;
-                transform.translation.y = hover_point.y;
+                translation.x = hover_point.x;
+                translation.y = hover_point.y;
             }
+            card_animations.select.tick(time.delta())
         } else {
-            z_offset += card.animations.deselect.tick(time.delta());
-        }
+            card_animations.deselect.tick(time.delta())
+        };
 
-        if let Some(tile) = card.slotted_in_tile {
-            let tile_transform = transforms.get(tile).unwrap();
-            transform.translation.x = tile_transform.translation.x;
-            transform.translation.y = tile_transform.translation.y;
+        if let Some((tile, tile_transform)) =
+            card.slotted_in_tile.and_then(|tile| tiles.get(tile).ok())
+        {
+            let offset = tile
+                .slot_of(entity)
+                .map_or(Vec2::ZERO, |i| tile.slot_offset(i));
+            translation.x = tile_transform.translation.x + offset.x;
+            translation.y = tile_transform.translation.y + offset.y;
+        }
+        // only touch cards that moved, as the spatial index and transform propagation follow
+        // `Changed<Transform>`
+        if transform.translation != translation {
+            transform.translation = translation;
         }
-        transform.translation.z = z_offset;
     }
 
     for root in stack_roots.roots.keys() {
@@ -444,14 +685,16 @@ fn move_cards(
 }
 
 fn position_stack(
-    cards: &mut Query<(Entity, &mut Card, &mut Transform)>,
+    cards: &mut Query<(Entity, &Card, &mut Transform)>,
     entity: Entity,
     root_position: Vec3,
     depth: usize,
 ) {
     let child = if let Ok((_, card, mut transform)) = cards.get_mut(entity) {
-        transform.translation =
-            root_position + Vec3::new(0.0, -0.3 * depth as f32, 0.01 * depth as f32);
+        let translation = root_position + Card::STACK_OFFSET * depth as f32;
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
         card.stack_child
     } else {
         None
@@ -462,72 +705,117 @@ fn position_stack(
     }
 }
 
-fn collide_cards(
-    mut commands: Commands,
-    mut collisions: EventReader<CollisionEvent>,
-    mut stack_roots: ResMut<StackRoots>,
-    mut selected: Res<SelectedCard>,
-    mut cards: Query<&mut Card>,
-    transforms: Query<&Transform>,
+/// The card that a card dropped at `position` would be stacked on: the top of the stack under that
+/// point. The dropped card, everything stacked on it and dying cards are ignored, and nothing can
+/// be dropped onto a stack that is slotted in a tile.
+pub fn find_drop_target(
+    spatial_index: &SpatialIndex,
+    cards: &Query<&Card>,
+    dying: &Query<(), With<Dying>>,
+    dropped: Entity,
+    position: Vec3,
+) -> Option<Entity> {
+    let mut substack = HashSet::new();
+    let mut current = Some(dropped);
+    while let Some(entity) = current {
+        substack.insert(entity);
+        current = cards.get(entity).ok().and_then(|card| card.stack_child);
+    }
+
+    let hit = spatial_index.at(position.truncate(), Card::SIZE, |entity| {
+        !substack.contains(&entity) && !dying.contains(entity)
+    })?;
+
+    let top = find_stack_top(cards, hit);
+    let (dropped_card, top_card) = (cards.get(dropped).ok()?, cards.get(top).ok()?);
+    // only the root of a squad assaulting an enemy tile is slotted in it
+    let root_card = cards.get(find_stack_root(cards, hit)).ok()?;
+    if dropped_card.stack_parent.is_none()
+        && dropped_card.is_stackable()
+        && top_card.is_stackable()
+        && root_card.slotted_in_tile.is_none()
+        && !dying.contains(top)
+    {
+        Some(top)
+    } else {
+        None
+    }
+}
+
+/// Takes a single card out of its stack, linking the cards below and above it back together.
+pub fn unstack_single(
+    cards: &mut Query<&mut Card>,
+    stack_roots: &mut StackRoots,
+    events: &mut EventWriter<CardUnstacked>,
+    entity: Entity,
 ) {
-    let mut stack_x_on_y = Vec::new();
-    for collision in collisions.iter() {
-        match *collision {
-            CollisionEvent::Started(e1, e2, _) => {
-                if selected.is_selected(e1) || selected.is_selected(e2) {
-                    continue;
-                }
-                if let (Ok([mut c1, mut c2]), Ok([t1, t2])) =
-                    (cards.get_many_mut([e1, e2]), transforms.get_many([e1, e2]))
-                {
-                    if t1.translation.z > t2.translation.z {
-                        if c1.stack_parent.is_none() {
-                            stack_x_on_y.push((e1, e2));
-                        }
-                    } else {
-                        if c2.stack_parent.is_none() {
-                            stack_x_on_y.push((e2, e1));
-                        }
-                    }
-                }
+    let (parent, child) = match cards.get_mut(entity) {
+        Ok(mut card) => (card.stack_parent.take(), card.stack_child.take()),
+        Err(_) => return,
+    };
+    if let Some(stack) = parent.or(child) {
+        events.send(CardUnstacked {
+            card: entity,
+            stack,
+        });
+    }
+    // close the gap left by the card
+    if let Some(parent) = parent {
+        if let Ok(mut card) = cards.get_mut(parent) {
+            card.stack_child = child;
+        }
+        stack_roots.queued_stack_recomputations.insert(parent);
+    }
+    if let Some(child) = child {
+        if let Ok(mut card) = cards.get_mut(child) {
+            card.stack_parent = parent;
+        }
+        // the card was the root, so the card above it takes over the stack
+        // and the card's own stack type is cancelled by its recomputation
+        if parent.is_none() {
+            stack_roots.roots.insert(child, StackType::Pending);
+            stack_roots.queued_stack_recomputations.insert(child);
+            stack_roots.queued_stack_recomputations.insert(entity);
+        }
+    }
+}
+
+/// Puts the stack rooted at `entity` on top of `top`, which must be the top of its stack.
+fn stack_onto(
+    cards: &mut Query<&mut Card>,
+    stack_roots: &mut StackRoots,
+    events: &mut EventWriter<CardStacked>,
+    entity: Entity,
+    top: Entity,
+) {
+    if let Ok([mut card, mut top_card]) = cards.get_many_mut([entity, top]) {
+        // update pointers
+        top_card.stack_child = Some(entity);
+        card.stack_parent = Some(top);
+        events.send(CardStacked {
+            card: entity,
+            onto: top,
+        });
+
+        match stack_roots.roots.entry(top) {
+            // if stack root is already a stack, queue recalculation
+            Entry::Occupied(_) => {
+                stack_roots.queued_stack_recomputations.insert(top);
             }
-            _ => {}
-        }
-    }
-
-    for (ex, ey) in stack_x_on_y {
-        let top = find_stack_top(&cards.to_readonly(), ey);
-        if let Ok([mut cx, mut ctop]) = cards.get_many_mut([ex, top]) {
-            if cx.stack_parent.is_none()
-                && ctop.stack_child.is_none()
-                && ctop.is_stackable()
-                && cx.is_stackable()
-            {
-                // update pointers
-                ctop.stack_child = Some(ex);
-                cx.stack_parent = Some(top);
-
-                match stack_roots.roots.entry(top) {
-                    // if stack root is already a stack, queue recalculation
-                    Entry::Occupied(_) => {
-                        stack_roots.queued_stack_recomputations.insert(top);
-                    }
-                    // if parent is newly stacked, make it a stack root and recompute
-                    Entry::Vacant(mut entry) => {
-                        entry.insert(StackType::Pending);
-                        stack_roots.queued_stack_recomputations.insert(top);
-                    }
-                }
+            // if parent is newly stacked, make it a stack root and recompute
+            Entry::Vacant(entry) => {
+                entry.insert(StackType::Pending);
+                stack_roots.queued_stack_recomputations.insert(top);
+            }
+        }
 
-                match stack_roots.roots.entry(ex) {
-                    // if newly stacked card is a stack, queue it for recomputation (and therefore removal)
-                    Entry::Occupied(_) => {
-                        stack_roots.queued_stack_recomputations.insert(ex);
-                    }
-                    // if newly stacked card is not a stack, do nothing
-                    Entry::Vacant(_) => {}
-                }
+        match stack_roots.roots.entry(entity) {
+            // if newly stacked card is a stack, queue it for recomputation (and therefore removal)
+            Entry::Occupied(_) => {
+                stack_roots.queued_stack_recomputations.insert(entity);
             }
+            // if newly stacked card is not a stack, do nothing
+            Entry::Vacant(_) => {}
         }
     }
 }
@@ -546,6 +834,21 @@ fn find_stack_top(cards: &Query<&Card>, mut current_entity: Entity) -> Entity {
     }
 }
 
+/// Whether `root` is the bottom of a stack of nothing but villagers, which can be sent to assault
+/// an enemy tile.
+pub fn is_villager_squad(cards: &Query<&Card>, root: Entity) -> bool {
+    let mut current = Some(root);
+    while let Some(entity) = current {
+        match cards.get(entity) {
+            Ok(card) if card.class() == CardClass::Villager => current = card.stack_child,
+            _ => return false,
+        }
+    }
+    cards
+        .get(root)
+        .is_ok_and(|card| card.stack_parent.is_none())
+}
+
 fn find_stack_root(cards: &Query<&Card>, mut current_entity: Entity) -> Entity {
     loop {
         if let Ok(card) = cards.get(current_entity) {
@@ -560,21 +863,15 @@ fn find_stack_root(cards: &Query<&Card>, mut current_entity: Entity) -> Entity {
     }
 }
 
-pub fn select_card(
-    mut commands: Commands,
-    context: Res<RapierContext>,
+/// Projects the cursor onto the board. Replays set the hover point from their actions instead,
+/// so a held card follows the recording rather than wherever the mouse happens to be.
+fn update_hover_point(
     windows: Res<Windows>,
-    hovered_tile: Res<HoveredTile>,
-    mouse: Res<Input<MouseButton>>,
-    mut selected_card: ResMut<SelectedCard>,
-    mut stack_roots: ResMut<StackRoots>,
     mut hover_point: ResMut<HoverPoint>,
     cameras: Query<(&Camera, &Transform), With<PlayerCamera>>,
-    mut cards: Query<&mut Card>,
-    mut tiles: Query<(&mut Tile, &Transform)>,
 ) {
     let window = windows.primary();
-    if let Some(mut cursor) = window.cursor_position() {
+    if let Some(cursor) = window.cursor_position() {
         let (camera, camera_transform) = cameras.single();
 
         let view = camera_transform.compute_matrix();
@@ -603,95 +900,253 @@ pub fn select_card(
         } else {
             *hover_point = HoverPoint::None;
         }
+    }
+}
+
+fn mouse_actions(
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    hover_point: Res<HoverPoint>,
+    selected_card: Res<SelectedCard>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    if mouse.just_pressed(MouseButton::Left) {
+        if let HoverPoint::Some(position) = *hover_point {
+            let mode = if keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+                PickMode::Single
+            } else {
+                PickMode::Stack
+            };
+            actions.send(PlayerAction::PickCard { position, mode });
+        }
+    }
+
+    if mouse.just_released(MouseButton::Left) && *selected_card != SelectedCard::None {
+        let position = match *hover_point {
+            HoverPoint::Some(position) => Some(position),
+            HoverPoint::None => None,
+        };
+        actions.send(PlayerAction::DropCard { position });
+    }
+}
+
+/// Marks the outline showing where the selected card will land when dropped.
+#[derive(Component)]
+struct DropPreview;
+
+fn spawn_drop_preview(
+    mut commands: Commands,
+    card_data: Res<CardData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: card_data.mesh.clone(),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(1.0, 1.0, 1.0, 0.3),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(DropPreview);
+}
+
+fn update_drop_preview(
+    spatial_index: Res<SpatialIndex>,
+    selected_card: Res<SelectedCard>,
+    hover_point: Res<HoverPoint>,
+    cards: Query<&Card>,
+    dying: Query<(), With<Dying>>,
+    transforms: Query<&Transform, Without<DropPreview>>,
+    mut previews: Query<(&mut Transform, &mut Visibility), With<DropPreview>>,
+) {
+    let target = match (*selected_card, &*hover_point) {
+        (SelectedCard::Some(entity), HoverPoint::Some(position)) => {
+            find_drop_target(&spatial_index, &cards, &dying, entity, *position)
+        }
+        _ => None,
+    };
+    let top = target.and_then(|top| transforms.get(top).ok());
 
-        if mouse.just_pressed(MouseButton::Left) {
-            let result = context.cast_ray(near, direction, 50.0, true, QueryFilter::new());
+    for (mut transform, mut visibility) in &mut previews {
+        match top {
+            Some(top) => {
+                transform.translation = top.translation + Card::STACK_OFFSET;
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+}
 
-            if let Some((entity, toi)) = result {
-                if cards.get(entity).unwrap().is_player_controlled() {
-                    let (parent, child) = {
+pub fn select_card(
+    mut commands: Commands,
+    mut actions: EventReader<PlayerAction>,
+    spatial_index: Res<SpatialIndex>,
+    tile_grid: Res<TileGrid>,
+    config: Res<GameConfig>,
+    mut selected_card: ResMut<SelectedCard>,
+    mut stack_roots: ResMut<StackRoots>,
+    mut hover_point: ResMut<HoverPoint>,
+    mut cards: Query<&mut Card>,
+    mut transforms: Query<&mut Transform, With<Card>>,
+    mut tiles: Query<(&mut Tile, &Transform), Without<Card>>,
+    dying: Query<(), With<Dying>>,
+    mut stacked_events: EventWriter<CardStacked>,
+    mut unstacked_events: EventWriter<CardUnstacked>,
+    mut slotted_events: EventWriter<CardSlotted>,
+    state: Res<State<GameState>>,
+) {
+    // the editor can move any card around, enemies included
+    let editing = *state.current() == GameState::Editing;
+    for action in actions.iter() {
+        match *action {
+            PlayerAction::PickCard { position, mode } => {
+                *hover_point = HoverPoint::Some(position);
+                // look straight down onto the board, so picking doesn't depend on the camera
+                let result = spatial_index.at(position.truncate(), Card::SIZE, |_| true);
+
+                if let Some(entity) = result {
+                    if cards
+                        .get(entity)
+                        .is_ok_and(|card| editing || card.is_player_controlled())
+                        && dying.get(entity).is_err()
+                    {
                         let mut card = cards.get_mut(entity).unwrap();
                         // unslot from tile
-                        if let Some(tile_entity) = card.slotted_in_tile {
-                            card.slotted_in_tile = None;
+                        if let Some(tile_entity) = card.slotted_in_tile.take() {
                             let (mut tile, _) = tiles.get_mut(tile_entity).unwrap();
-                            match &mut *tile {
-                                Tile::Woods {
-                                    slotted_villager,
-                                    progress_bar,
-                                } => {
-                                    *slotted_villager = None;
-                                    if let Some(progress_bar) = *progress_bar {
-                                        commands.entity(progress_bar).despawn_recursive();
+                            tile.unslot_card(&mut commands, entity);
+                        }
+                        *selected_card = SelectedCard::Some(entity);
+                        // finish unstack
+                        match mode {
+                            PickMode::Stack => {
+                                let child = card.stack_child;
+                                if let Some(parent) = card.stack_parent.take() {
+                                    unstacked_events.send(CardUnstacked {
+                                        card: entity,
+                                        stack: parent,
+                                    });
+                                    if let Ok(mut parent_card) = cards.get_mut(parent) {
+                                        parent_card.stack_child = None;
+                                    }
+                                    // queue parent for recomputation
+                                    stack_roots.queued_stack_recomputations.insert(parent);
+
+                                    // unstacked card is now a stack root, create a new stack root as pending and recompute
+                                    if child.is_some() {
+                                        stack_roots.roots.insert(entity, StackType::Pending);
+                                        stack_roots.queued_stack_recomputations.insert(entity);
                                     }
                                 }
-                                _ => {}
                             }
-                        }
-                        card.animations.select.reset();
-                        *selected_card = SelectedCard::Some(entity);
-                        let parent = card.stack_parent;
-                        card.stack_parent = None;
-                        (parent, card.stack_child)
-                    };
-                    // finish unstack
-                    if let Some(parent) = parent {
-                        let mut card = cards.get_mut(parent).unwrap();
-                        card.stack_child = None;
-                        // queue parent for recomputation
-                        stack_roots.queued_stack_recomputations.insert(parent);
-
-                        // unstacked card is now a stack root, create a new stack root as pending and recompute
-                        if child.is_some() {
-                            stack_roots.roots.insert(entity, StackType::Pending);
-                            stack_roots.queued_stack_recomputations.insert(entity);
+                            PickMode::Single => {
+                                unstack_single(
+                                    &mut cards,
+                                    &mut stack_roots,
+                                    &mut unstacked_events,
+                                    entity,
+                                );
+                            }
                         }
                     }
                 }
             }
-        }
-    }
+            PlayerAction::DropCard { position } => {
+                if let Some(position) = position {
+                    *hover_point = HoverPoint::Some(position);
+                }
+                let entity = match *selected_card {
+                    SelectedCard::Some(entity) => entity,
+                    SelectedCard::None => continue,
+                };
+                *selected_card = SelectedCard::None;
+                if !cards.contains(entity) {
+                    continue;
+                }
+                let position = match position {
+                    Some(position) => position,
+                    None => continue,
+                };
+                if let Ok(mut transform) = transforms.get_mut(entity) {
+                    transform.translation.x = position.x;
+                    transform.translation.y = position.y;
+                }
 
-    if mouse.just_released(MouseButton::Left) {
-        if let SelectedCard::Some(entity) = *selected_card {
-            let mut card = cards.get_mut(entity).unwrap();
-            card.animations.deselect.reset();
-            *selected_card = SelectedCard::None;
-            // try stacking on a tile
-            if !card.in_stack() {
-                if let Some(tile_entity) = hovered_tile.0 {
-                    if let Ok((mut tile, transform)) = tiles.get_mut(tile_entity) {
-                        if let HoverPoint::Some(hover_point) = *hover_point {
-                            let slot_size = Tile::slot_size();
-                            if transform.translation.x - slot_size.x / 2.0 < hover_point.x
-                                && hover_point.x < transform.translation.x + slot_size.x / 2.0
-                                && transform.translation.y - slot_size.y / 2.0 < hover_point.y
-                                && hover_point.y < transform.translation.y + slot_size.y / 2.0
-                            {
-                                if tile.try_slotting_card(&mut commands, tile_entity, entity, &card)
-                                {
-                                    card.slotted_in_tile = Some(tile_entity);
-                                }
-                            }
+                // stack on the card under the cursor
+                if let Some(top) = find_drop_target(
+                    &spatial_index,
+                    &cards.to_readonly(),
+                    &dying,
+                    entity,
+                    position,
+                ) {
+                    stack_onto(
+                        &mut cards,
+                        &mut stack_roots,
+                        &mut stacked_events,
+                        entity,
+                        top,
+                    );
+                    continue;
+                }
+
+                // try slotting into a tile, or assaulting it with a squad of villagers
+                let squad = is_villager_squad(&cards.to_readonly(), entity);
+                let mut card = cards.get_mut(entity).unwrap();
+                let location = Tile::translation_to_grid(position);
+                if let Some(tile_entity) = tile_grid.get(&location).copied() {
+                    if let Ok((mut tile, tile_transform)) = tiles.get_mut(tile_entity) {
+                        let slotted = if matches!(*tile, Tile::Enemies { .. }) {
+                            squad && tile.try_assaulting(entity)
+                        } else if !card.in_stack() {
+                            let offset = (position - tile_transform.translation).truncate();
+                            tile.slot_at(offset).is_some_and(|index| {
+                                tile.try_slotting_card(
+                                    &mut commands,
+                                    tile_entity,
+                                    index,
+                                    entity,
+                                    &card,
+                                    &config,
+                                )
+                            })
+                        } else {
+                            false
+                        };
+                        if slotted {
+                            card.slotted_in_tile = Some(tile_entity);
+                            slotted_events.send(CardSlotted {
+                                card: entity,
+                                tile: tile_entity,
+                            });
                         }
                     }
                 }
             }
+            PlayerAction::MoveCamera { .. } => {}
         }
     }
 }
 
-fn evaluate_stacks(
+pub fn evaluate_stacks(
     mut commands: Commands,
-    time: Res<Time>,
+    clock: Res<SimulationClock>,
+    recipes: Res<Recipes>,
     mut stack_roots: ResMut<StackRoots>,
-    cards: Query<&Card>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut cards: Query<&mut Card>,
     mut progress_bars: Query<&mut ProgressBar>,
     transforms: Query<&Transform>,
+    mut stacked_events: EventWriter<CardStacked>,
 ) {
     let stack_roots = &mut *stack_roots;
     for entity in stack_roots.queued_stack_recomputations.drain() {
-        let root = find_stack_root(&cards, entity);
+        let root = find_stack_root(&cards.to_readonly(), entity);
         let mut cancelled_stack_types = Vec::new();
         if root != entity {
             // if the queued entity is no longer a root, remove the root and cancel the current stack_type
@@ -700,29 +1155,16 @@ fn evaluate_stacks(
             }
         }
         // if the queued root is still a root, recompute the stack type
-        let card_types = get_cards_types(root, &cards);
-        let villagers = card_types.get(&CardType::Villager).unwrap_or(&0);
-        let new_stack_type = if *villagers == 2 && card_types.len() == 1 {
-            let mut progress_bar = None;
-            commands.entity(root).with_children(|parent| {
-                progress_bar = Some(
-                    parent
-                        .spawn_bundle(ProgressBarBundle {
-                            progress_bar: ProgressBar {
-                                current: 0.0,
-                                total: 5.0,
-                                width: 0.7,
-                                height: 0.15,
-                                padding: 0.05,
-                            },
-                            transform: Transform::from_xyz(0.0, 0.55, 0.0),
-                            ..default()
-                        })
-                        .id(),
-                );
-            });
-            StackType::Breed {
-                progress_bar: progress_bar.unwrap(),
+        let card_types = get_cards_types(root, &cards.to_readonly());
+        let new_stack_type = if let Some(recipe) = recipes.find(&card_types) {
+            StackType::Recipe {
+                recipe,
+                progress_bar: spawn_recipe_progress_bar(
+                    &mut commands,
+                    root,
+                    recipes[recipe].duration,
+                    0.0,
+                ),
             }
         } else {
             StackType::Nothing
@@ -737,59 +1179,186 @@ fn evaluate_stacks(
             match stack_type {
                 StackType::Pending => {}
                 StackType::Nothing => {}
-                StackType::Breed { progress_bar } => {
+                StackType::Recipe { progress_bar, .. } => {
                     commands.entity(progress_bar).despawn_recursive();
                 }
             }
         }
     }
 
-    let mut queued_recomputations = Vec::new();
+    let mut finished_recipes = Vec::new();
     for (root, stack_type) in stack_roots.roots.iter_mut() {
-        let mut should_reset = false;
         match stack_type {
             StackType::Pending => {}
             StackType::Nothing => {}
-            StackType::Breed { progress_bar } => {
+            StackType::Recipe {
+                recipe,
+                progress_bar,
+            } => {
                 if let Ok(mut bar) = progress_bars.get_mut(*progress_bar) {
-                    bar.add(time.delta_seconds());
+                    // squads assaulting an enemy tile have their hands full
+                    let paused = cards
+                        .get(*root)
+                        .is_ok_and(|card| card.slotted_in_tile.is_some());
+                    let state = if is_stack_in_combat(&cards.to_readonly(), *root) {
+                        ProgressBarState::Blocked
+                    } else {
+                        ProgressBarState::Working
+                    };
+                    if bar.paused != paused || bar.state != state {
+                        bar.paused = paused;
+                        bar.state = state;
+                    }
+                    if bar.is_advancing() {
+                        bar.tick(clock.delta_seconds());
+                    }
                     if bar.finished() {
                         commands.entity(*progress_bar).despawn_recursive();
-                        if let Ok(transform) = transforms.get(*root) {
-                            commands.spawn_bundle(CardBundle {
-                                card: Card {
-                                    info: CardType::Villager.into(),
-                                    ..default()
-                                },
-                                transform: Transform::from_xyz(
-                                    transform.translation.x + Card::SPAWN_OFFSET,
-                                    transform.translation.y,
-                                    0.0,
-                                ),
-                                ..default()
-                            });
-                        }
-                        should_reset = true;
+                        finished_recipes.push((*root, *recipe));
+                        *stack_type = StackType::Pending;
                     }
                 }
             }
         }
-        if should_reset {
-            *stack_type = StackType::Pending;
-            queued_recomputations.push(*root);
+    }
+
+    for (root, recipe) in finished_recipes {
+        let recipe = &recipes[recipe];
+        let translation = transforms.get(root).map(|transform| transform.translation);
+
+        let new_root = consume_recipe_inputs(&mut commands, &mut cards, root, recipe);
+        if new_root != Some(root) {
+            stack_roots.roots.remove(&root);
+        }
+        if let Some(new_root) = new_root {
+            stack_roots.queued_stack_recomputations.insert(new_root);
+        }
+
+        if let Ok(translation) = translation {
+            // outputs are dealt next to the stack, unless the recipe puts them on top of it
+            let stack_top = new_root
+                .filter(|_| recipe.stack_outputs)
+                .map(|new_root| find_stack_top(&cards.to_readonly(), new_root));
+            let outputs: Vec<Entity> = recipe
+                .outputs
+                .iter()
+                .map(|_| commands.spawn().id())
+                .collect();
+            for (i, (output, entity)) in recipe.outputs.iter().zip(&outputs).enumerate() {
+                let mut card = Card::from(output.clone());
+                let mut position = translation.truncate() + Vec2::X * Card::SPAWN_OFFSET;
+                if let Some(stack_top) = stack_top {
+                    let onto = if i == 0 { stack_top } else { outputs[i - 1] };
+                    card.stack_parent = Some(onto);
+                    card.stack_child = outputs.get(i + 1).copied();
+                    stacked_events.send(CardStacked {
+                        card: *entity,
+                        onto,
+                    });
+                    position.x += Card::SPAWN_OFFSET * i as f32;
+                } else {
+                    // dealt cards don't land on top of each other or on other cards
+                    position = spatial_index.free_position_near(position, Card::SPAWN_OFFSET);
+                    spatial_index.insert(*entity, position.extend(0.0));
+                }
+                commands.entity(*entity).insert_bundle(CardBundle {
+                    card,
+                    transform: Transform::from_translation(position.extend(0.0)),
+                    ..default()
+                });
+            }
+            if let (Some(stack_top), Some(first)) = (stack_top, outputs.first()) {
+                cards.get_mut(stack_top).unwrap().stack_child = Some(*first);
+            }
+        }
+    }
+}
+
+pub fn spawn_recipe_progress_bar(
+    commands: &mut Commands,
+    root: Entity,
+    total: f32,
+    current: f32,
+) -> Entity {
+    let mut progress_bar = None;
+    commands.entity(root).with_children(|parent| {
+        progress_bar = Some(
+            parent
+                .spawn_bundle(ProgressBarBundle {
+                    progress_bar: ProgressBar {
+                        current,
+                        total,
+                        width: 0.25,
+                        padding: 0.02,
+                        style: ProgressBarStyle::Radial,
+                        ..default()
+                    },
+                    // in the top right corner, clear of the title
+                    transform: Transform::from_xyz(0.25, 0.4, 0.0),
+                    ..default()
+                })
+                .id(),
+        );
+    });
+    progress_bar.unwrap()
+}
+
+/// Whether any card in the stack rooted at `root` is fighting.
+fn is_stack_in_combat(cards: &Query<&Card>, root: Entity) -> bool {
+    let mut current = Some(root);
+    while let Some(card) = current.and_then(|entity| cards.get(entity).ok()) {
+        if card.combat_state.is_some() {
+            return true;
+        }
+        current = card.stack_child;
+    }
+    false
+}
+
+/// Despawns the cards consumed by `recipe` and re-links the kept cards into a single stack.
+/// Returns the root of the remaining stack, if any cards were kept.
+fn consume_recipe_inputs(
+    commands: &mut Commands,
+    cards: &mut Query<&mut Card>,
+    root: Entity,
+    recipe: &Recipe,
+) -> Option<Entity> {
+    let mut consumed = recipe.consumed_counts();
+    if consumed.is_empty() {
+        return Some(root);
+    }
+
+    let mut kept = Vec::new();
+    let mut current = Some(root);
+    while let Some(entity) = current {
+        let card = match cards.get(entity) {
+            Ok(card) => card,
+            Err(_) => break,
+        };
+        current = card.stack_child;
+        match consumed.get_mut(card.card_type()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                commands.entity(entity).despawn_recursive();
+            }
+            _ => kept.push(entity),
         }
     }
 
-    stack_roots
-        .queued_stack_recomputations
-        .extend(queued_recomputations);
+    for (i, entity) in kept.iter().enumerate() {
+        let mut card = cards.get_mut(*entity).unwrap();
+        card.stack_parent = i.checked_sub(1).map(|parent| kept[parent]);
+        card.stack_child = kept.get(i + 1).copied();
+    }
+
+    kept.first().copied()
 }
 
 fn get_cards_types(root: Entity, cards: &Query<&Card>) -> HashMap<CardType, usize> {
     let mut current = root;
     let mut card_types = HashMap::new();
     while let Ok(card) = cards.get(current) {
-        let mut count = card_types.entry(card.card_type()).or_insert(0);
+        let count = card_types.entry(card.card_type().clone()).or_insert(0);
         *count += 1;
         if let Some(child) = card.stack_child {
             current = child;
@@ -801,15 +1370,44 @@ fn get_cards_types(root: Entity, cards: &Query<&Card>) -> HashMap<CardType, usiz
     card_types
 }
 
+/// Kept apart from [`Card`] as it's ticked every frame, so `Changed<Card>` only picks up changes
+/// to the card itself.
+#[derive(Component)]
 pub struct Animations {
     select: AnimateRange,
     deselect: AnimateRange,
     attack_in: AnimateRange,
     attack_out: AnimateRange,
+    attack_direction: Vec3,
+    hit: Timer,
+}
+
+impl Animations {
+    pub const HIT_FLASH: f32 = 0.15;
+
+    /// Lunges towards `direction`, then back.
+    pub fn attack(&mut self, direction: Vec3) {
+        self.attack_direction = direction;
+        self.attack_in.reset();
+        self.attack_out.reset();
+    }
+
+    pub fn hit(&mut self) {
+        self.hit.reset();
+    }
 }
 
 impl Default for Animations {
     fn default() -> Self {
+        let mut attack_in =
+            AnimateRange::new(Duration::from_secs_f32(0.2), Ease::Linear, 1.0..1.5, false);
+        let mut attack_out =
+            AnimateRange::new(Duration::from_secs_f32(0.2), Ease::Linear, 1.5..1.0, false);
+        // nothing to play until the card attacks
+        attack_in.finish();
+        attack_out.finish();
+        let mut hit = Timer::from_seconds(Self::HIT_FLASH, false);
+        hit.tick(hit.duration());
         Self {
             select: AnimateRange::new(Duration::from_secs_f32(0.2), Ease::Linear, 0.0..0.5, false),
             deselect: AnimateRange::new(
@@ -818,115 +1416,10 @@ impl Default for Animations {
                 0.5..0.0,
                 false,
             ),
-            attack_in: AnimateRange::new(
-                Duration::from_secs_f32(0.2),
-                Ease::Linear,
-                1.0..1.5,
-                false,
-            ),
-            attack_out: AnimateRange::new(
-                Duration::from_secs_f32(0.2),
-                Ease::Linear,
-                1.5..1.0,
-                false,
-            ),
-        }
-    }
-}
-
-pub fn handle_enemies(time: Res<Time>, mut cards: Query<(Entity, &mut Card, &mut Transform)>) {
-    let mut enemy_targets = Vec::new();
-    for (entity, card, transform) in &cards {
-        if card.combat_state.is_some() {
-            continue;
-        }
-        if let CardClass::Enemy = card.class() {
-            let mut current_target: Option<(Entity, Vec3)> = None;
-            for (target_entity, target_card, target_transform) in &cards {
-                if target_card.class() == CardClass::Villager {
-                    if let Some((_, current_translation)) = current_target {
-                        if current_translation.distance_squared(transform.translation)
-                            > target_transform
-                                .translation
-                                .distance_squared(transform.translation)
-                        {
-                            current_target = Some((target_entity, target_transform.translation));
-                        }
-                    } else {
-                        current_target = Some((target_entity, target_transform.translation));
-                    }
-                }
-            }
-
-            if let Some((target, translation)) = current_target {
-                enemy_targets.push((entity, target, translation))
-            }
-        }
-    }
-
-    for (enemy, target, target_translation) in enemy_targets {
-        let [(_, mut card, mut transform), (_, mut target_card, _)] =
-            cards.get_many_mut([enemy, target]).unwrap();
-        let distance = target_translation - transform.translation;
-        // move until close
-        if distance.length() > 1.0 {
-            let direction = distance.normalize();
-            transform.translation += direction * time.delta_seconds();
-            card.combat_state = None;
-        } else {
-            card.combat_state = Some(CombatState {
-                cooldown: Timer::from_seconds(1.0, true),
-                target,
-            });
-
-            // if target_card.combat_state.is_none() {
-            //     target_card.combat_state = Some(CombatState {
-            //         // villagers attack faster than enemies
-            //         cooldown: Timer::from_seconds(0.9, true),
-            //         target: enemy,
-            //     });
-            // }
-        }
-    }
-}
-
-fn combat(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut cards: Query<&mut Card>,
-    card_entities: Query<Entity, With<Card>>,
-) {
-    for entity in &card_entities {
-        let result = {
-            let mut card = cards.get_mut(entity).unwrap();
-            if let Some(combat_state) = &mut card.combat_state {
-                if combat_state.cooldown.tick(time.delta()).just_finished() {
-                    Some((combat_state.target, card.info.stats.damage))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        };
-
-        if let Some((damaged_entity, damage)) = result {
-            if let Ok([mut target_card, mut card]) = cards.get_many_mut([damaged_entity, entity]) {
-                target_card.info.stats.health =
-                    (target_card.info.stats.health - damage as isize).max(0);
-                if target_card.combat_state.is_none() {
-                    target_card.combat_state = Some(CombatState {
-                        cooldown: Timer::from_seconds(0.9, true),
-                        target: entity,
-                    });
-                }
-                if target_card.info.stats.health == 0 {
-                    card.combat_state = None;
-                    commands.entity(damaged_entity).despawn_recursive();
-                }
-            } else {
-                cards.get_mut(entity).unwrap().combat_state = None;
-            }
+            attack_in,
+            attack_out,
+            attack_direction: Vec3::ZERO,
+            hit,
         }
     }
 }
@@ -0,0 +1,192 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use crate::game::{
+    card::{Card, CardClass, CardInfo, CardStats, CardType},
+    combat::Dying,
+};
+
+pub const CARD_DEFINITIONS_PATH: &str = "base.cards.ron";
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "5c1e62b5-8d3f-4f3c-9d0c-3b8a7f0e2a41"]
+pub struct CardDefinitions(pub Vec<CardDefinition>);
+
+#[derive(Deserialize, Clone)]
+pub struct CardDefinition {
+    pub id: CardType,
+    pub name: String,
+    pub class: CardClass,
+    #[serde(default)]
+    pub health: usize,
+    #[serde(default)]
+    pub damage: usize,
+    #[serde(default)]
+    pub attack_speed: f32,
+    #[serde(default = "default_work_speed")]
+    pub work_speed: f32,
+    pub portrait: String,
+    pub tint: Color,
+}
+
+impl CardDefinition {
+    pub fn stats(&self) -> CardStats {
+        CardStats {
+            health: self.health as isize,
+            max_health: self.health,
+            damage: self.damage,
+            attack_speed: self.attack_speed,
+            work_speed: self.work_speed,
+        }
+    }
+
+    /// Brings a live card in line with this definition. Damage the card has taken carries over,
+    /// so its health is the new max health minus that damage, but never drops below 1: editing a
+    /// definition shouldn't heal cards back up or kill them outright.
+    pub fn apply(&self, info: &mut CardInfo) {
+        let damage_taken = info.stats.max_health as isize - info.stats.health;
+        let mut stats = self.stats();
+        if stats.max_health > 0 {
+            stats.health = (stats.health - damage_taken).max(1);
+        }
+        info.class = self.class;
+        info.stats = stats;
+    }
+}
+
+fn default_work_speed() -> f32 {
+    1.0
+}
+
+#[derive(Default)]
+pub struct CardDefinitionsLoader;
+
+impl AssetLoader for CardDefinitionsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definitions = ron::de::from_bytes::<CardDefinitions>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definitions));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cards.ron"]
+    }
+}
+
+/// The card definitions currently in use, keyed by id. Rebuilt whenever the definitions asset is
+/// (re)loaded, so edits to the asset file show up in a running game, including on cards that are
+/// already on the board (see [`CardDefinition::apply`]).
+pub struct CardRegistry {
+    handle: Handle<CardDefinitions>,
+    definitions: HashMap<CardType, CardDefinition>,
+}
+
+impl FromWorld for CardRegistry {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self {
+            handle: asset_server.load(CARD_DEFINITIONS_PATH),
+            definitions: HashMap::new(),
+        }
+    }
+}
+
+impl CardRegistry {
+    pub fn is_loaded(&self) -> bool {
+        !self.definitions.is_empty()
+    }
+
+    pub fn get(&self, card_type: &CardType) -> Option<&CardDefinition> {
+        self.definitions.get(card_type)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CardDefinition> {
+        self.definitions.values()
+    }
+}
+
+pub fn update_card_registry(
+    mut events: EventReader<AssetEvent<CardDefinitions>>,
+    mut registry: ResMut<CardRegistry>,
+    definitions: Res<Assets<CardDefinitions>>,
+    mut cards: Query<&mut Card, Without<Dying>>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if *handle != registry.handle {
+                    continue;
+                }
+                if let Some(loaded) = definitions.get(handle) {
+                    registry.definitions = loaded
+                        .0
+                        .iter()
+                        .map(|definition| (definition.id.clone(), definition.clone()))
+                        .collect();
+                }
+                if matches!(event, AssetEvent::Modified { .. }) {
+                    for mut card in &mut cards {
+                        if let Some(definition) = registry.definitions.get(card.card_type()) {
+                            definition.apply(&mut card.info);
+                        }
+                    }
+                }
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(health: usize) -> CardDefinition {
+        CardDefinition {
+            id: CardType::VILLAGER,
+            name: "Villager".to_string(),
+            class: CardClass::Villager,
+            health,
+            damage: 1,
+            attack_speed: 1.0,
+            work_speed: 1.0,
+            portrait: String::new(),
+            tint: Color::WHITE,
+        }
+    }
+
+    fn damaged(max_health: usize, health: isize) -> CardInfo {
+        let mut info = CardInfo::from(CardType::VILLAGER);
+        info.stats.max_health = max_health;
+        info.stats.health = health;
+        info
+    }
+
+    #[test]
+    fn apply_keeps_damage_taken() {
+        let mut info = damaged(3, 1);
+        definition(5).apply(&mut info);
+        assert_eq!(info.stats.max_health, 5);
+        assert_eq!(info.stats.health, 3);
+        assert_eq!(info.class, CardClass::Villager);
+    }
+
+    #[test]
+    fn apply_never_kills() {
+        let mut info = damaged(5, 1);
+        definition(2).apply(&mut info);
+        assert_eq!(info.stats.max_health, 2);
+        assert_eq!(info.stats.health, 1);
+    }
+}
@@ -0,0 +1,383 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::game::{
+    card::{
+        evaluate_stacks, unstack_single, Animations, Card, CardClass, SelectedCard, StackRoots,
+    },
+    events::{CardDamaged, CardDied, CardUnstacked},
+    simulation::{SimulationClock, SimulationStage},
+    spatial::SpatialIndex,
+    state::GameState,
+    tile::Tile,
+};
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(SimulationStage, hunt_villagers.after(evaluate_stacks))
+            .add_system_to_stage(SimulationStage, join_engagements.after(hunt_villagers))
+            .add_system_to_stage(SimulationStage, fight.after(join_engagements))
+            .add_system_to_stage(SimulationStage, remove_dead_cards.after(fight));
+    }
+}
+
+/// A fight between the enemies and villagers in it. Every card attacks the first card on the
+/// other side, so villagers joining a fight gang up on the same enemy. The engagement ends when
+/// either side is empty.
+#[derive(Component, Default, Debug)]
+pub struct Engagement {
+    pub enemies: Vec<Entity>,
+    pub villagers: Vec<Entity>,
+}
+
+impl Engagement {
+    pub const ENGAGE_DISTANCE: f32 = 1.0;
+    /// Cards further than this from every opponent leave the fight.
+    pub const DISENGAGE_DISTANCE: f32 = 2.0;
+
+    fn side(&mut self, class: CardClass) -> Option<&mut Vec<Entity>> {
+        match class {
+            CardClass::Villager => Some(&mut self.villagers),
+            CardClass::Enemy => Some(&mut self.enemies),
+            CardClass::Resource => None,
+        }
+    }
+
+    fn opponent(&self, class: CardClass) -> Option<Entity> {
+        match class {
+            CardClass::Villager => self.enemies.first().copied(),
+            CardClass::Enemy => self.villagers.first().copied(),
+            CardClass::Resource => None,
+        }
+    }
+
+    fn members(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.enemies.iter().chain(&self.villagers).copied()
+    }
+
+    fn is_over(&self) -> bool {
+        self.enemies.is_empty() || self.villagers.is_empty()
+    }
+}
+
+/// Added to a card's [`Card::combat_state`] while it is in an [`Engagement`].
+pub struct CombatState {
+    pub engagement: Entity,
+    /// `None` for cards without an attack speed, which only take hits.
+    pub cooldown: Option<Timer>,
+}
+
+impl CombatState {
+    fn new(engagement: Entity, card: &Card) -> Self {
+        let attack_speed = card.info.stats.attack_speed;
+        Self {
+            engagement,
+            cooldown: (attack_speed > 0.0).then(|| Timer::from_seconds(1.0 / attack_speed, true)),
+        }
+    }
+}
+
+/// Cards with no health left. They are taken off the board right away and despawned once their
+/// death animation has played.
+#[derive(Component)]
+pub struct Dying {
+    timer: Timer,
+}
+
+impl Default for Dying {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(Self::DURATION, false),
+        }
+    }
+}
+
+impl Dying {
+    pub const DURATION: f32 = 0.5;
+
+    pub fn percent(&self) -> f32 {
+        self.timer.percent()
+    }
+}
+
+/// Enemies that aren't fighting walk towards the nearest villager and start or join its fight
+/// once they reach it.
+pub fn hunt_villagers(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    selected_card: Res<SelectedCard>,
+    spatial_index: Res<SpatialIndex>,
+    mut engagements: Query<&mut Engagement>,
+    mut cards: Query<(Entity, &mut Card, &mut Transform), Without<Dying>>,
+) {
+    let mut enemy_targets = Vec::new();
+    for (entity, card, transform) in &cards {
+        if card.combat_state.is_some() || card.class() != CardClass::Enemy {
+            continue;
+        }
+        let target = spatial_index.nearest(transform.translation.truncate(), |target| {
+            *selected_card != SelectedCard::Some(target)
+                && cards
+                    .get(target)
+                    .is_ok_and(|(_, card, _)| card.class() == CardClass::Villager)
+        });
+        if let Some((target, _)) = target {
+            let translation = cards.get(target).unwrap().2.translation;
+            enemy_targets.push((entity, target, translation));
+        }
+    }
+
+    // engagements started this step, their components only exist once commands are applied
+    let mut started: HashMap<Entity, Engagement> = HashMap::new();
+    for (enemy, target, target_translation) in enemy_targets {
+        let [(_, mut card, mut transform), (_, mut target_card, _)] =
+            cards.get_many_mut([enemy, target]).unwrap();
+        let distance = target_translation - transform.translation;
+        // move until close
+        if distance.length() > Engagement::ENGAGE_DISTANCE {
+            let direction = distance.normalize();
+            transform.translation += direction * clock.delta_seconds();
+            continue;
+        }
+
+        let current_engagement = target_card
+            .combat_state
+            .as_ref()
+            .map(|combat_state| combat_state.engagement);
+        let engagement = match current_engagement {
+            Some(engagement) => engagement,
+            None => {
+                let engagement = commands.spawn().id();
+                started.insert(
+                    engagement,
+                    Engagement {
+                        enemies: Vec::new(),
+                        villagers: vec![target],
+                    },
+                );
+                target_card.combat_state = Some(CombatState::new(engagement, &target_card));
+                debug!("{:?} started a fight with {:?}", enemy, target);
+                engagement
+            }
+        };
+        match engagements.get_mut(engagement) {
+            Ok(mut engagement) => engagement.enemies.push(enemy),
+            Err(_) => started.get_mut(&engagement).unwrap().enemies.push(enemy),
+        }
+        card.combat_state = Some(CombatState::new(engagement, &card));
+    }
+
+    for (entity, engagement) in started {
+        commands.entity(entity).insert(engagement);
+    }
+}
+
+/// Villagers close to a fighting enemy join its fight.
+fn join_engagements(
+    selected_card: Res<SelectedCard>,
+    spatial_index: Res<SpatialIndex>,
+    mut engagements: Query<&mut Engagement>,
+    mut cards: Query<(Entity, &mut Card, &Transform), Without<Dying>>,
+) {
+    let fighting_enemies: Vec<(Entity, Vec3)> = cards
+        .iter()
+        .filter_map(|(_, card, transform)| {
+            let combat_state = card.combat_state.as_ref()?;
+            (card.class() == CardClass::Enemy)
+                .then_some((combat_state.engagement, transform.translation))
+        })
+        .collect();
+
+    for (engagement_entity, enemy_translation) in fighting_enemies {
+        // engagements started this step don't have their component yet
+        let mut engagement = match engagements.get_mut(engagement_entity) {
+            Ok(engagement) => engagement,
+            Err(_) => continue,
+        };
+        let nearby =
+            spatial_index.within(enemy_translation.truncate(), Engagement::ENGAGE_DISTANCE);
+        for (entity, _) in nearby {
+            if *selected_card == SelectedCard::Some(entity) {
+                continue;
+            }
+            if let Ok((_, mut card, transform)) = cards.get_mut(entity) {
+                if card.class() != CardClass::Villager
+                    || card.combat_state.is_some()
+                    || transform.translation.distance(enemy_translation)
+                        > Engagement::ENGAGE_DISTANCE
+                {
+                    continue;
+                }
+                engagement.villagers.push(entity);
+                card.combat_state = Some(CombatState::new(engagement_entity, &card));
+                debug!("{:?} joined fight {:?}", entity, engagement_entity);
+            }
+        }
+    }
+}
+
+pub fn fight(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    selected_card: Res<SelectedCard>,
+    mut engagements: Query<(Entity, &mut Engagement)>,
+    mut cards: Query<(&mut Card, &Transform)>,
+    mut animations: Query<&mut Animations>,
+    mut damaged_events: EventWriter<CardDamaged>,
+    mut died_events: EventWriter<CardDied>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let mut villager_died = false;
+    for (engagement_entity, mut engagement) in &mut engagements {
+        // cards that were dragged away, moved out of reach or despawned leave the fight
+        let positions: Vec<(Entity, CardClass, Vec3)> = engagement
+            .members()
+            .filter_map(|member| {
+                let (card, transform) = cards.get(member).ok()?;
+                Some((member, card.class(), transform.translation))
+            })
+            .collect();
+        let mut leaving = Vec::new();
+        for member in engagement.members() {
+            let in_reach = positions
+                .iter()
+                .find(|(entity, _, _)| *entity == member)
+                .is_some_and(|(_, class, translation)| {
+                    positions.iter().any(|(_, other_class, other_translation)| {
+                        other_class != class
+                            && translation.distance(*other_translation)
+                                <= Engagement::DISENGAGE_DISTANCE
+                    })
+                });
+            if !in_reach || *selected_card == SelectedCard::Some(member) {
+                leaving.push(member);
+            }
+        }
+        for member in leaving {
+            engagement.enemies.retain(|entity| *entity != member);
+            engagement.villagers.retain(|entity| *entity != member);
+            if let Ok((mut card, _)) = cards.get_mut(member) {
+                card.combat_state = None;
+                debug!("{:?} left fight {:?}", member, engagement_entity);
+            }
+        }
+
+        let members: Vec<Entity> = engagement.members().collect();
+        for member in members {
+            let attack = match cards.get_mut(member) {
+                Ok((mut card, transform)) if card.info.stats.health > 0 => {
+                    let class = card.class();
+                    let damage = card.info.stats.damage;
+                    let cooldown = card
+                        .combat_state
+                        .as_mut()
+                        .and_then(|combat_state| combat_state.cooldown.as_mut());
+                    let ready = cooldown
+                        .is_some_and(|cooldown| cooldown.tick(clock.delta()).just_finished());
+                    if ready {
+                        engagement
+                            .opponent(class)
+                            .map(|target| (target, damage, transform.translation))
+                    } else {
+                        None
+                    }
+                }
+                // killed earlier this step
+                _ => None,
+            };
+
+            if let Some((target, damage, translation)) = attack {
+                if let Ok((mut target_card, target_transform)) = cards.get_mut(target) {
+                    let direction =
+                        (target_transform.translation - translation).normalize_or_zero();
+                    if let Ok(mut target_animations) = animations.get_mut(target) {
+                        target_animations.hit();
+                    }
+                    let stats = &mut target_card.info.stats;
+                    stats.health = (stats.health - damage as isize).max(0);
+                    damaged_events.send(CardDamaged {
+                        card: target,
+                        attacker: member,
+                        damage,
+                    });
+                    if target_card.info.stats.health == 0 {
+                        let class = target_card.class();
+                        if let Some(side) = engagement.side(class) {
+                            side.retain(|entity| *entity != target);
+                        }
+                        villager_died |= class == CardClass::Villager;
+                        target_card.combat_state = None;
+                        commands.entity(target).insert(Dying::default());
+                        died_events.send(CardDied {
+                            card: target,
+                            card_type: target_card.card_type().clone(),
+                            killer: member,
+                        });
+                        debug!("{:?} was killed by {:?}", target, member);
+                    }
+                    if let Ok(mut member_animations) = animations.get_mut(member) {
+                        member_animations.attack(direction);
+                    }
+                }
+            }
+        }
+
+        if engagement.is_over() {
+            for member in engagement.members() {
+                if let Ok((mut card, _)) = cards.get_mut(member) {
+                    card.combat_state = None;
+                }
+            }
+            commands.entity(engagement_entity).despawn();
+            debug!("fight {:?} is over", engagement_entity);
+        }
+    }
+
+    let villagers_left = cards
+        .iter()
+        .any(|(card, _)| card.class() == CardClass::Villager && card.info.stats.health > 0);
+    if villager_died && !villagers_left {
+        info!("the last villager died");
+        // overwrite so a pause requested on the same frame doesn't keep the game going
+        let _ = state.overwrite_set(GameState::GameOver);
+    }
+}
+
+fn remove_dead_cards(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    mut stack_roots: ResMut<StackRoots>,
+    mut dead: Query<(Entity, &mut Dying)>,
+    mut cards: Query<&mut Card>,
+    mut tiles: Query<&mut Tile>,
+    mut unstacked_events: EventWriter<CardUnstacked>,
+) {
+    for (entity, mut dying) in &mut dead {
+        if dying.is_added() {
+            let slotted_in_tile = cards
+                .get_mut(entity)
+                .ok()
+                .and_then(|mut card| card.slotted_in_tile.take());
+            if let Some(mut tile) = slotted_in_tile.and_then(|tile| tiles.get_mut(tile).ok()) {
+                tile.unslot_card(&mut commands, entity);
+            }
+            unstack_single(&mut cards, &mut stack_roots, &mut unstacked_events, entity);
+        }
+
+        if dying.timer.tick(clock.delta()).just_finished() {
+            // nothing should have been stacked on the card since, but don't leave links to a
+            // despawned entity behind if something was
+            for mut card in &mut cards {
+                if card.stack_parent == Some(entity) {
+                    card.stack_parent = None;
+                }
+                if card.stack_child == Some(entity) {
+                    card.stack_child = None;
+                }
+            }
+            stack_roots.remove(entity);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{card::CardType, objective::Objective, recipe::Recipe, tile::TileKind};
+
+/// How a game starts, how fast the board produces and what it takes to win. Passed to
+/// [`GamePlugin`](crate::game::GamePlugin) and available as a resource. Scenario files are this
+/// struct in RON; fields left out keep their defaults. The board editor reads and writes the same
+/// files.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub cards: Vec<StartingCard>,
+    pub stacks: Vec<StartingStack>,
+    pub tiles: Vec<StartingTile>,
+    /// The game is won once all of these are met. Without any, it only ends when every villager
+    /// has died.
+    pub objectives: Vec<Objective>,
+    /// Seconds a slotted villager takes to make a card, for tiles whose
+    /// [`Production::duration`](crate::game::tile::Production::duration) should be overridden.
+    pub production_durations: BTreeMap<TileKind, f32>,
+    /// Seconds between goblins coming out of an enemy tile.
+    pub enemies_duration: f32,
+    /// Damage it takes to clear an enemy tile.
+    pub enemy_tile_health: u32,
+    /// Seconds a recipe takes, by name, for recipes whose duration in the recipes asset should be
+    /// overridden.
+    pub recipe_durations: BTreeMap<String, f32>,
+    pub enemy_tiles: EnemyTileConfig,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartingCard {
+    pub card_type: CardType,
+    pub position: [f32; 2],
+}
+
+/// Cards dealt already stacked, listed from the bottom up.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartingStack {
+    pub cards: Vec<CardType>,
+    pub position: [f32; 2],
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartingTile {
+    pub kind: TileKind,
+    pub location: [i32; 2],
+}
+
+/// See [`EnemyTileSpawner::new`](crate::game::tile::EnemyTileSpawner::new).
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnemyTileConfig {
+    pub interval: f32,
+    pub min_start_distance: i32,
+    pub seed: u64,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        let start = (-1..2).flat_map(|x| {
+            (-1..2).map(move |y| StartingTile {
+                kind: match [x, y] {
+                    [-1, -1] => TileKind::Quarry,
+                    [1, -1] => TileKind::Farm,
+                    [-1, 1] => TileKind::BerryBush,
+                    [1, 1] => TileKind::River,
+                    _ => TileKind::Woods,
+                },
+                location: [x, y],
+            })
+        });
+        Self {
+            cards: vec![
+                StartingCard {
+                    card_type: CardType::VILLAGER,
+                    position: [-0.5, 0.0],
+                },
+                StartingCard {
+                    card_type: CardType::VILLAGER,
+                    position: [0.5, 0.0],
+                },
+            ],
+            stacks: Vec::new(),
+            tiles: start
+                .chain([StartingTile {
+                    kind: TileKind::Enemies,
+                    location: [0, 2],
+                }])
+                .collect(),
+            objectives: Vec::new(),
+            production_durations: BTreeMap::new(),
+            enemies_duration: 20.0,
+            enemy_tile_health: 30,
+            recipe_durations: BTreeMap::new(),
+            enemy_tiles: EnemyTileConfig::default(),
+        }
+    }
+}
+
+impl Default for EnemyTileConfig {
+    fn default() -> Self {
+        Self {
+            interval: 60.0,
+            min_start_distance: 3,
+            seed: 0,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        Ok(ron::de::from_bytes(&fs::read(path)?)?)
+    }
+
+    pub fn production_duration(&self, kind: TileKind) -> f32 {
+        match self.production_durations.get(&kind) {
+            Some(duration) => *duration,
+            None => kind
+                .production()
+                .map_or(0.0, |production| production.duration),
+        }
+    }
+
+    pub fn recipe_duration(&self, recipe: &Recipe) -> f32 {
+        match self.recipe_durations.get(recipe.name.as_ref()) {
+            Some(duration) => *duration,
+            None => recipe.duration,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), anyhow::Error> {
+        fs::write(path, ron::ser::to_string_pretty(self, PrettyConfig::new())?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use crate::game::{
+    card::{unstack_single, Card, CardBundle, CardType, HoverPoint, SelectedCard, StackRoots},
+    card_definition::CardRegistry,
+    config::{GameConfig, StartingCard, StartingStack, StartingTile},
+    events::CardUnstacked,
+    objective::Objective,
+    spatial::SpatialIndex,
+    state::GameState,
+    tile::{Tile, TileBundle, TileGrid, TileGridLocation, TileKind},
+};
+
+/// Board editor for scenario files. Cards are moved and stacked with the mouse like in a game,
+/// the keyboard places and removes the rest:
+///
+/// - Tab: pick the next card type to place
+/// - C: place a card under the cursor
+/// - X or Delete: remove the card under the cursor
+/// - T: cycle the tile under the cursor through every [`TileKind`] and nothing
+/// - O: add an objective to collect one card of the current type, or one more if there is one
+/// - V: add an objective to survive for a minute, or a minute more if there is one
+/// - Backspace: remove the last objective
+/// - F5: write the board to the scenario file
+/// - F9: discard changes and reload the scenario file
+///
+/// Production settings are kept as they were read and have to be edited in the file itself.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorBrush>()
+            .add_system_set(SystemSet::on_enter(GameState::Editing).with_system(spawn_editor_help))
+            .add_system_set(SystemSet::on_exit(GameState::Editing).with_system(despawn_editor_help))
+            .add_system_set(
+                SystemSet::on_update(GameState::Editing)
+                    .with_system(cycle_brush)
+                    .with_system(place_card)
+                    .with_system(remove_card)
+                    .with_system(cycle_tile)
+                    .with_system(edit_objectives)
+                    .with_system(write_scenario.after(edit_objectives))
+                    .with_system(reload_scenario)
+                    .with_system(
+                        update_editor_help
+                            .after(cycle_brush)
+                            .after(edit_objectives)
+                            .after(reload_scenario),
+                    ),
+            );
+    }
+}
+
+/// The scenario file being edited. Inserted by `--edit <file>`, which starts in the editor
+/// instead of the main menu.
+pub struct EditorSettings {
+    pub path: PathBuf,
+}
+
+/// The card type placed by the editor.
+#[derive(Default)]
+pub struct EditorBrush(pub CardType);
+
+#[derive(Component)]
+struct EditorHelp;
+
+fn cycle_brush(
+    input: Res<Input<KeyCode>>,
+    card_registry: Res<CardRegistry>,
+    mut brush: ResMut<EditorBrush>,
+) {
+    if !input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    let mut card_types: Vec<&CardType> = card_registry
+        .iter()
+        .map(|definition| &definition.id)
+        .collect();
+    card_types.sort_by(|a, b| a.0.cmp(&b.0));
+    let next = card_types
+        .iter()
+        .position(|card_type| **card_type == brush.0)
+        .map_or(0, |i| (i + 1) % card_types.len());
+    if let Some(card_type) = card_types.get(next) {
+        brush.0 = (*card_type).clone();
+    }
+}
+
+fn place_card(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    hover_point: Res<HoverPoint>,
+    brush: Res<EditorBrush>,
+) {
+    if !input.just_pressed(KeyCode::C) {
+        return;
+    }
+    if let HoverPoint::Some(point) = *hover_point {
+        commands.spawn_bundle(CardBundle {
+            transform: Transform::from_xyz(point.x, point.y, 0.0),
+            card: Card::from(brush.0.clone()),
+            ..default()
+        });
+    }
+}
+
+fn remove_card(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    hover_point: Res<HoverPoint>,
+    spatial_index: Res<SpatialIndex>,
+    mut selected_card: ResMut<SelectedCard>,
+    mut stack_roots: ResMut<StackRoots>,
+    mut cards: Query<&mut Card>,
+    mut tiles: Query<&mut Tile>,
+    mut unstacked_events: EventWriter<CardUnstacked>,
+) {
+    if !input.any_just_pressed([KeyCode::X, KeyCode::Delete]) {
+        return;
+    }
+    let point = match *hover_point {
+        HoverPoint::Some(point) => point,
+        HoverPoint::None => return,
+    };
+    let entity = match spatial_index.at(point.truncate(), Card::SIZE, |entity| {
+        cards.get(entity).is_ok()
+    }) {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    let slotted_in_tile = cards.get_mut(entity).unwrap().slotted_in_tile.take();
+    if let Some(mut tile) = slotted_in_tile.and_then(|tile| tiles.get_mut(tile).ok()) {
+        tile.unslot_card(&mut commands, entity);
+    }
+    unstack_single(&mut cards, &mut stack_roots, &mut unstacked_events, entity);
+    stack_roots.remove(entity);
+    if *selected_card == SelectedCard::Some(entity) {
+        *selected_card = SelectedCard::None;
+    }
+    commands.entity(entity).despawn_recursive();
+}
+
+fn cycle_tile(
+    mut commands: Commands,
+    input: Res<Input<KeyCode>>,
+    hover_point: Res<HoverPoint>,
+    mut tile_grid: ResMut<TileGrid>,
+    mut cards: Query<&mut Card>,
+    tiles: Query<&Tile>,
+) {
+    if !input.just_pressed(KeyCode::T) {
+        return;
+    }
+    let location = match *hover_point {
+        HoverPoint::Some(point) => Tile::translation_to_grid(point),
+        HoverPoint::None => return,
+    };
+
+    let current = tile_grid.get(&location).copied();
+    let current_kind = current
+        .and_then(|entity| tiles.get(entity).ok())
+        .map(|tile| tile.kind());
+    let next_kind = match current_kind {
+        None => TileKind::ALL.first().copied(),
+        Some(kind) => {
+            let i = TileKind::ALL
+                .iter()
+                .position(|other| *other == kind)
+                .unwrap();
+            TileKind::ALL.get(i + 1).copied()
+        }
+    };
+
+    if let Some(entity) = current {
+        if let Ok(tile) = tiles.get(entity) {
+            let squad = match tile {
+                Tile::Enemies { squad, .. } => *squad,
+                Tile::Producer { .. } => None,
+            };
+            for villager in tile.slotted_villagers().iter().flatten().chain(&squad) {
+                if let Ok(mut card) = cards.get_mut(*villager) {
+                    card.slotted_in_tile = None;
+                }
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+        tile_grid.remove(&location);
+    }
+    if let Some(kind) = next_kind {
+        commands.spawn_bundle(TileBundle {
+            tile: kind.into(),
+            tile_grid_location: TileGridLocation(location),
+            ..default()
+        });
+    }
+}
+
+/// Seconds added to the survive objective by each press of V.
+const SURVIVE_STEP: f64 = 60.0;
+
+fn edit_objectives(
+    input: Res<Input<KeyCode>>,
+    brush: Res<EditorBrush>,
+    mut config: ResMut<GameConfig>,
+) {
+    if input.just_pressed(KeyCode::O) {
+        let collect = config
+            .objectives
+            .iter_mut()
+            .find_map(|objective| match objective {
+                Objective::Collect { card_type, count } if *card_type == brush.0 => Some(count),
+                _ => None,
+            });
+        match collect {
+            Some(count) => *count += 1,
+            None => config.objectives.push(Objective::Collect {
+                card_type: brush.0.clone(),
+                count: 1,
+            }),
+        }
+    }
+    if input.just_pressed(KeyCode::V) {
+        let survive = config
+            .objectives
+            .iter_mut()
+            .find_map(|objective| match objective {
+                Objective::Survive { seconds } => Some(seconds),
+                _ => None,
+            });
+        match survive {
+            Some(seconds) => *seconds += SURVIVE_STEP,
+            None => config.objectives.push(Objective::Survive {
+                seconds: SURVIVE_STEP,
+            }),
+        }
+    }
+    if input.just_pressed(KeyCode::Back) {
+        config.objectives.pop();
+    }
+}
+
+fn write_scenario(
+    input: Res<Input<KeyCode>>,
+    settings: Option<Res<EditorSettings>>,
+    mut config: ResMut<GameConfig>,
+    cards: Query<(&Card, &Transform)>,
+    tiles: Query<(&Tile, &TileGridLocation)>,
+) {
+    let settings = match settings {
+        Some(settings) if input.just_pressed(KeyCode::F5) => settings,
+        _ => return,
+    };
+
+    let mut new_config = config.clone();
+    new_config.cards.clear();
+    new_config.stacks.clear();
+    for (card, transform) in &cards {
+        if card.stack_parent.is_some() {
+            continue;
+        }
+        let position = [transform.translation.x, transform.translation.y];
+        let mut stack = vec![card.card_type().clone()];
+        let mut current = card.stack_child;
+        while let Some((child, _)) = current.and_then(|child| cards.get(child).ok()) {
+            stack.push(child.card_type().clone());
+            current = child.stack_child;
+        }
+        if stack.len() == 1 {
+            new_config.cards.push(StartingCard {
+                card_type: stack.remove(0),
+                position,
+            });
+        } else {
+            new_config.stacks.push(StartingStack {
+                cards: stack,
+                position,
+            });
+        }
+    }
+    // query order depends on spawn history, sort to keep diffs of the file small
+    new_config
+        .cards
+        .sort_by(|a, b| a.position[0].total_cmp(&b.position[0]));
+    new_config
+        .stacks
+        .sort_by(|a, b| a.position[0].total_cmp(&b.position[0]));
+    new_config.tiles = tiles
+        .iter()
+        .map(|(tile, location)| StartingTile {
+            kind: tile.kind(),
+            location: location.0.to_array(),
+        })
+        .collect();
+    new_config.tiles.sort_by_key(|tile| tile.location);
+
+    match new_config.write(&settings.path) {
+        Ok(()) => {
+            *config = new_config;
+            info!("wrote scenario to {}", settings.path.display());
+        }
+        Err(err) => error!(
+            "failed to write scenario to {}: {}",
+            settings.path.display(),
+            err
+        ),
+    }
+}
+
+fn reload_scenario(
+    input: Res<Input<KeyCode>>,
+    settings: Option<Res<EditorSettings>>,
+    mut config: ResMut<GameConfig>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let settings = match settings {
+        Some(settings) if input.just_pressed(KeyCode::F9) => settings,
+        _ => return,
+    };
+    match GameConfig::read(&settings.path) {
+        Ok(new_config) => {
+            *config = new_config;
+            // re-entering the editor deals the board again
+            state.restart().unwrap();
+            info!("reloaded scenario from {}", settings.path.display());
+        }
+        Err(err) => error!(
+            "failed to read scenario from {}: {}",
+            settings.path.display(),
+            err
+        ),
+    }
+}
+
+fn spawn_editor_help(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/DejaVuSans.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(EditorHelp);
+}
+
+fn despawn_editor_help(mut commands: Commands, help: Query<Entity, With<EditorHelp>>) {
+    for entity in &help {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update_editor_help(
+    brush: Res<EditorBrush>,
+    config: Res<GameConfig>,
+    settings: Option<Res<EditorSettings>>,
+    new_help: Query<(), Added<EditorHelp>>,
+    mut help: Query<&mut Text, With<EditorHelp>>,
+) {
+    if !brush.is_changed() && !config.is_changed() && new_help.is_empty() {
+        return;
+    }
+    let path = settings.map_or_else(
+        || "no file, start with --edit <file> to save".to_string(),
+        |settings| settings.path.display().to_string(),
+    );
+    let objectives = if config.objectives.is_empty() {
+        "none".to_string()
+    } else {
+        config
+            .objectives
+            .iter()
+            .map(|objective| objective.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    for mut text in &mut help {
+        text.sections[0].value = format!(
+            "Editing {}\n\
+             Tab: card type ({})  C: place card  X: remove card\n\
+             T: cycle tile  F5: save  F9: reload\n\
+             O: collect objective  V: survive objective  Backspace: remove objective\n\
+             Objectives: {}",
+            path, brush.0 .0, objectives
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::game::card::CardType;
+
+/// Gameplay events sent by the core systems, so UI, audio, stats and other plugins can react to
+/// what happens on the board without touching those systems.
+pub struct GameEventsPlugin;
+
+impl Plugin for GameEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CardSpawned>()
+            .add_event::<CardStacked>()
+            .add_event::<CardUnstacked>()
+            .add_event::<CardSlotted>()
+            .add_event::<TileProduced>()
+            .add_event::<TileExhausted>()
+            .add_event::<TileCleared>()
+            .add_event::<CardDamaged>()
+            .add_event::<CardDied>();
+    }
+}
+
+/// Sent once for every new card, including cards restored from a save.
+pub struct CardSpawned {
+    pub card: Entity,
+    pub card_type: CardType,
+}
+
+/// `card` (and anything stacked on it) was put on top of `onto`.
+pub struct CardStacked {
+    pub card: Entity,
+    pub onto: Entity,
+}
+
+/// `card` was taken out of a stack. `stack` is a card that is still in it.
+pub struct CardUnstacked {
+    pub card: Entity,
+    pub stack: Entity,
+}
+
+pub struct CardSlotted {
+    pub card: Entity,
+    pub tile: Entity,
+}
+
+pub struct TileProduced {
+    pub tile: Entity,
+    pub card: Entity,
+    pub card_type: CardType,
+}
+
+/// A producing tile ran out of stock and put its villagers out of their slots.
+pub struct TileExhausted {
+    pub tile: Entity,
+}
+
+/// A squad cleared an enemy tile. `tile` is despawned and `woods` takes its place.
+pub struct TileCleared {
+    pub tile: Entity,
+    pub woods: Entity,
+    pub squad: Entity,
+}
+
+pub struct CardDamaged {
+    pub card: Entity,
+    pub attacker: Entity,
+    pub damage: usize,
+}
+
+/// Sent when a card runs out of health, before its death animation plays.
+pub struct CardDied {
+    pub card: Entity,
+    pub card_type: CardType,
+    pub killer: Entity,
+}
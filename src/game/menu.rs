@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+
+use crate::game::state::{AfterLoading, GameState};
+
+/// Full screen overlays for every [`GameState`] but [`GameState::Playing`], and the keys that move
+/// between them.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuData>()
+            .insert_resource(AfterLoading(GameState::MainMenu))
+            .add_system(menu_input);
+        for state in [
+            GameState::Loading,
+            GameState::MainMenu,
+            GameState::Paused,
+            GameState::GameOver,
+            GameState::Won,
+        ] {
+            app.add_system_set(SystemSet::on_enter(state).with_system(show_screen))
+                .add_system_set(SystemSet::on_exit(state).with_system(hide_screen));
+        }
+    }
+}
+
+struct MenuData {
+    font: Handle<Font>,
+}
+
+impl FromWorld for MenuData {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self {
+            font: asset_server.load("fonts/DejaVuSans.ttf"),
+        }
+    }
+}
+
+#[derive(Component)]
+struct MenuScreen;
+
+fn menu_input(keyboard: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    let result = match *state.current() {
+        GameState::MainMenu if keyboard.just_pressed(KeyCode::Return) => {
+            state.set(GameState::Playing)
+        }
+        GameState::Playing if keyboard.any_just_pressed([KeyCode::Escape, KeyCode::P]) => {
+            state.push(GameState::Paused)
+        }
+        GameState::Paused if keyboard.any_just_pressed([KeyCode::Escape, KeyCode::P]) => {
+            state.pop()
+        }
+        GameState::Paused if keyboard.just_pressed(KeyCode::Q) => {
+            state.replace(GameState::MainMenu)
+        }
+        GameState::GameOver | GameState::Won if keyboard.just_pressed(KeyCode::R) => {
+            state.set(GameState::Playing)
+        }
+        GameState::GameOver | GameState::Won if keyboard.just_pressed(KeyCode::Escape) => {
+            state.set(GameState::MainMenu)
+        }
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        warn!("ignored menu input: {:?}", err);
+    }
+}
+
+fn show_screen(mut commands: Commands, menu_data: Res<MenuData>, state: Res<State<GameState>>) {
+    let (title, lines): (&str, &[&str]) = match state.current() {
+        GameState::Loading => ("Loading...", &[]),
+        GameState::MainMenu => ("Card Combinator", &["Press Enter to start"]),
+        GameState::Paused => ("Paused", &["Esc to resume", "Q to quit to the main menu"]),
+        GameState::GameOver => (
+            "Game Over",
+            &[
+                "All your villagers died",
+                "R to restart",
+                "Esc for the main menu",
+            ],
+        ),
+        GameState::Won => (
+            "Victory",
+            &[
+                "Every objective was met",
+                "R to play again",
+                "Esc for the main menu",
+            ],
+        ),
+        GameState::Playing | GameState::Editing => return,
+    };
+
+    let text_style = |font_size| TextStyle {
+        font: menu_data.font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                // columns grow upwards in bevy_ui
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(MenuScreen)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(title, text_style(60.0)));
+            for line in lines {
+                parent.spawn_bundle(
+                    TextBundle::from_section(*line, text_style(24.0)).with_style(Style {
+                        margin: UiRect::all(Val::Px(4.0)),
+                        ..default()
+                    }),
+                );
+            }
+        });
+}
+
+fn hide_screen(mut commands: Commands, screens: Query<Entity, With<MenuScreen>>) {
+    for entity in &screens {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,58 +1,196 @@
+pub mod action;
 pub mod animate;
 pub mod camera;
 pub mod card;
+pub mod card_definition;
+pub mod combat;
+pub mod config;
+pub mod editor;
+pub mod events;
+pub mod menu;
+pub mod objective;
 pub mod progress_bar;
+pub mod recipe;
+pub mod replay;
+pub mod save;
+pub mod simulation;
+pub mod spatial;
+pub mod state;
+pub mod stress;
 pub mod tile;
 
-use std::f32::consts::PI;
-
 use self::camera::PlayerCameraPlugin;
 use crate::game::{
-    card::{Card, CardBundle, CardPlugin, CardType},
-    progress_bar::{ProgressBar, ProgressBarBundle, ProgressBarPlugin},
-    tile::TilePlugin,
+    action::ActionPlugin,
+    card::{
+        Card, CardBundle, CardPlugin, CardPresentationPlugin, SelectedCard, StackRoots, StackType,
+    },
+    combat::{CombatPlugin, Engagement},
+    config::GameConfig,
+    editor::EditorPlugin,
+    events::GameEventsPlugin,
+    menu::MenuPlugin,
+    objective::ObjectivePlugin,
+    progress_bar::ProgressBarPlugin,
+    replay::ReplayPlugin,
+    save::{SaveKeybindingsPlugin, SavePlugin},
+    simulation::{SimulationPlugin, TimeScaleControlsPlugin},
+    spatial::SpatialIndexPlugin,
+    state::{GameState, GameStatePlugin},
+    tile::{EnemyTileSpawner, HoveredTile, Tile, TileGrid, TilePlugin, TilePresentationPlugin},
+};
+use bevy::{
+    asset::AssetPlugin, hierarchy::HierarchyPlugin, prelude::*, transform::TransformPlugin,
 };
-use bevy::prelude::*;
 
-pub struct GamePlugin;
+/// The full game: simulation plus rendering and player input.
+#[derive(Default)]
+pub struct GamePlugin {
+    pub config: GameConfig,
+}
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(CardPlugin)
+        app.add_plugin(GameLogicPlugin {
+            config: self.config.clone(),
+        })
+        .add_plugin(GamePresentationPlugin);
+    }
+}
+
+/// Tiles, stacks, recipes, enemies and combat. Only needs `MinimalPlugins`, the asset server and
+/// transform propagation, so it can run without a window or GPU.
+#[derive(Default)]
+pub struct GameLogicPlugin {
+    pub config: GameConfig,
+}
+
+impl Plugin for GameLogicPlugin {
+    fn build(&self, app: &mut App) {
+        // other plugins read the config while building
+        app.insert_resource(self.config.clone())
+            .add_plugin(SimulationPlugin)
+            .add_plugin(GameStatePlugin)
+            .add_plugin(ActionPlugin)
+            .add_plugin(GameEventsPlugin)
+            .add_plugin(CardPlugin)
+            .add_plugin(CombatPlugin)
+            .add_plugin(ObjectivePlugin)
+            .add_plugin(TilePlugin)
+            .add_plugin(SpatialIndexPlugin)
+            .add_plugin(SavePlugin)
+            .add_plugin(ReplayPlugin)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(clear_board)
+                    .with_system(setup.after(clear_board)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Editing)
+                    .with_system(clear_board)
+                    .with_system(setup.after(clear_board)),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(clear_board));
+    }
+}
+
+/// [`GameLogicPlugin`] along with the engine plugins it needs, for running without a window: the
+/// `--headless` flag and integration tests.
+#[derive(Default)]
+pub struct HeadlessPlugin {
+    pub config: GameConfig,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(GameLogicPlugin {
+                config: self.config.clone(),
+            });
+    }
+}
+
+/// Meshes, materials, the camera, menus and mouse/keyboard input.
+pub struct GamePresentationPlugin;
+
+impl Plugin for GamePresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(CardPresentationPlugin)
+            .add_plugin(TilePresentationPlugin)
             .add_plugin(PlayerCameraPlugin)
             .add_plugin(ProgressBarPlugin)
-            .add_plugin(TilePlugin)
-            .add_startup_system(setup);
+            .add_plugin(SaveKeybindingsPlugin)
+            .add_plugin(TimeScaleControlsPlugin)
+            .add_plugin(MenuPlugin)
+            .add_plugin(EditorPlugin);
     }
 }
 
-fn setup(
+/// Removes everything left over from the previous game. The new board is dealt by [`setup`] and
+/// [`tile::spawn_tiles`].
+fn clear_board(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut stack_roots: ResMut<StackRoots>,
+    mut tile_grid: ResMut<TileGrid>,
+    mut selected_card: ResMut<SelectedCard>,
+    mut hovered_tile: ResMut<HoveredTile>,
+    mut enemy_tile_spawner: ResMut<EnemyTileSpawner>,
+    board: Query<Entity, Or<(With<Card>, With<Tile>, With<Engagement>)>>,
 ) {
-    commands.spawn_bundle(CardBundle {
-        transform: Transform::from_xyz(-0.5, 0.0, 0.0),
-        card: Card::from(CardType::Villager),
-        ..default()
-    });
-    commands.spawn_bundle(CardBundle {
-        transform: Transform::from_xyz(0.5, 0.0, 0.0),
-        card: Card::from(CardType::Villager),
-        ..default()
-    });
+    for entity in &board {
+        commands.entity(entity).despawn_recursive();
+    }
+    stack_roots.clear();
+    tile_grid.clear();
+    *selected_card = SelectedCard::None;
+    hovered_tile.0 = None;
+    enemy_tile_spawner.reset();
+}
+
+fn setup(mut commands: Commands, config: Res<GameConfig>, mut stack_roots: ResMut<StackRoots>) {
+    for starting_card in &config.cards {
+        let [x, y] = starting_card.position;
+        commands.spawn_bundle(CardBundle {
+            transform: Transform::from_xyz(x, y, 0.0),
+            card: Card::from(starting_card.card_type.clone()),
+            ..default()
+        });
+    }
+
+    for stack in &config.stacks {
+        let [x, y] = stack.position;
+        // reserve the whole stack first so every card can link to its neighbours
+        let entities: Vec<Entity> = stack.cards.iter().map(|_| commands.spawn().id()).collect();
+        for (i, (card_type, entity)) in stack.cards.iter().zip(&entities).enumerate() {
+            commands.entity(*entity).insert_bundle(CardBundle {
+                transform: Transform::from_xyz(x, y, 0.0),
+                card: Card {
+                    stack_parent: i.checked_sub(1).map(|parent| entities[parent]),
+                    stack_child: entities.get(i + 1).copied(),
+                    ..Card::from(card_type.clone())
+                },
+                ..default()
+            });
+        }
+        if let [root, _, ..] = entities[..] {
+            stack_roots.insert(root, StackType::Pending);
+            stack_roots.queue_recomputation(root);
+        }
+    }
 
     // commands.spawn_bundle(CardBundle {
     //     transform: Transform::from_xyz(0.0, 3.0, 0.0),
-    //     card: Card::from(CardType::Goblin),
+    //     card: Card::from(CardType::GOBLIN),
     //     ..default()
     // });
 
     // commands.spawn_bundle(CardBundle {
     //     transform: Transform::from_xyz(1.0, 0.0, 0.0),
     //     card: Card {
-    //         card_type: CardType::Log,
+    //         card_type: CardType::LOG,
     //         ..default()
     //     },
     //     ..default()
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    card::{Card, CardType},
+    combat::{fight, Dying},
+    config::GameConfig,
+    simulation::{SimulationClock, SimulationStage},
+    state::GameState,
+};
+
+/// A goal set by the scenario. See [`GameConfig::objectives`].
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Objective {
+    /// Keep at least one villager alive for this long.
+    Survive { seconds: f64 },
+    /// Have at least `count` cards of `card_type` on the board at the same time.
+    Collect { card_type: CardType, count: usize },
+}
+
+impl Objective {
+    fn is_met(&self, clock: &SimulationClock, cards: &Query<&Card, Without<Dying>>) -> bool {
+        match self {
+            Objective::Survive { seconds } => clock.elapsed_seconds() >= *seconds,
+            Objective::Collect { card_type, count } => {
+                cards
+                    .iter()
+                    .filter(|card| card.card_type() == card_type)
+                    .count()
+                    >= *count
+            }
+        }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Objective::Survive { seconds } => write!(f, "survive {}s", seconds),
+            Objective::Collect { card_type, count } => {
+                write!(f, "collect {} {}", count, card_type.0)
+            }
+        }
+    }
+}
+
+pub struct ObjectivePlugin;
+
+impl Plugin for ObjectivePlugin {
+    fn build(&self, app: &mut App) {
+        // after combat, so losing the last villager wins over meeting the objectives on that step
+        app.add_system_to_stage(SimulationStage, check_objectives.after(fight));
+    }
+}
+
+fn check_objectives(
+    clock: Res<SimulationClock>,
+    config: Res<GameConfig>,
+    mut state: ResMut<State<GameState>>,
+    cards: Query<&Card, Without<Dying>>,
+) {
+    if config.objectives.is_empty()
+        || !config
+            .objectives
+            .iter()
+            .all(|objective| objective.is_met(&clock, &cards))
+    {
+        return;
+    }
+    if state.set(GameState::Won).is_ok() {
+        info!("all objectives met");
+    }
+}
@@ -1,25 +1,81 @@
-use bevy::prelude::{shape::Quad, *};
+use std::f32::consts::TAU;
+
+use bevy::{
+    prelude::{shape::Quad, *},
+    render::mesh::{Indices, PrimitiveTopology},
+    utils::HashMap,
+};
+
+use crate::game::camera::PlayerCamera;
 
 pub struct ProgressBarPlugin;
 
 impl Plugin for ProgressBarPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(on_spawn_progress_bar)
-            .add_system(set_progress_bar);
+        app.init_resource::<ProgressBarData>()
+            .add_system(on_spawn_progress_bar)
+            .add_system(set_progress_bar)
+            .add_system_to_stage(CoreStage::PostUpdate, update_progress_bar_labels);
     }
 }
 
-#[derive(Component, Default)]
+#[derive(Component)]
 pub struct ProgressBar {
     pub current: f32,
     pub total: f32,
     pub padding: f32,
+    /// The diameter of radial bars.
     pub width: f32,
     pub height: f32,
+    pub style: ProgressBarStyle,
+    pub state: ProgressBarState,
+    /// Paused bars don't advance and are drawn dimmed.
+    pub paused: bool,
+    /// Progress per second, for the remaining time shown by the label. Defaults to 1, so bars
+    /// measured in seconds tick in real time.
+    pub rate: f32,
+    /// Shows the time left next to the bar.
+    pub label: bool,
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self {
+            current: 0.0,
+            total: 0.0,
+            padding: 0.0,
+            width: 0.0,
+            height: 0.0,
+            style: default(),
+            state: default(),
+            paused: false,
+            rate: 1.0,
+            label: false,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProgressBarStyle {
+    #[default]
+    Linear,
+    /// A pie that fills clockwise, for timers small enough to sit in the corner of a card.
+    Radial,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProgressBarState {
+    #[default]
+    Working,
+    /// Something is keeping the bar from advancing, like the workers being in a fight.
+    Blocked,
 }
 
 impl ProgressBar {
     const Z: f32 = 0.1;
+    /// Working bars past this share of their total are drawn as almost done.
+    pub const ALMOST_DONE: f32 = 0.8;
+
     pub fn finished(&self) -> bool {
         self.current >= self.total
     }
@@ -29,14 +85,83 @@ impl ProgressBar {
         self.current = self.current.min(self.total);
     }
 
+    /// Advances by `seconds` at [`ProgressBar::rate`], unless paused or blocked.
+    pub fn tick(&mut self, seconds: f32) {
+        if self.is_advancing() {
+            self.add(seconds * self.rate);
+        }
+    }
+
+    /// Whether [`ProgressBar::tick`] would move the bar. Worth checking before taking the bar
+    /// mutably, as that alone marks it changed.
+    pub fn is_advancing(&self) -> bool {
+        !self.paused && self.state == ProgressBarState::Working && self.rate > 0.0
+    }
+
     pub fn reset(&mut self) {
         self.current = 0.0;
     }
+
+    pub fn percent(&self) -> f32 {
+        if self.total > 0.0 {
+            (self.current / self.total).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Seconds until the bar finishes at its current rate, if it's advancing at all.
+    pub fn remaining_seconds(&self) -> Option<f32> {
+        self.is_advancing()
+            .then(|| (self.total - self.current).max(0.0) / self.rate)
+    }
+
+    fn fill(&self) -> ProgressBarFill {
+        match self.state {
+            ProgressBarState::Blocked => ProgressBarFill::Blocked,
+            ProgressBarState::Working if self.percent() >= Self::ALMOST_DONE => {
+                ProgressBarFill::AlmostDone
+            }
+            ProgressBarState::Working => ProgressBarFill::Working,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ProgressBarFill {
+    Working,
+    Blocked,
+    AlmostDone,
+}
+
+impl ProgressBarFill {
+    const ALL: [ProgressBarFill; 3] = [
+        ProgressBarFill::Working,
+        ProgressBarFill::Blocked,
+        ProgressBarFill::AlmostDone,
+    ];
+
+    fn color(self) -> Color {
+        match self {
+            ProgressBarFill::Working => Color::rgb_u8(150, 150, 150),
+            ProgressBarFill::Blocked => Color::rgb_u8(170, 80, 70),
+            ProgressBarFill::AlmostDone => Color::rgb_u8(120, 170, 100),
+        }
+    }
 }
 
 #[derive(Component, Default)]
 pub struct ProgressBarStatus;
 
+#[derive(Component)]
+struct ProgressBarBackground;
+
+/// A UI text following a bar around the screen, see [`ProgressBar::label`].
+#[derive(Component)]
+struct ProgressBarLabel {
+    bar: Entity,
+}
+
 #[derive(Bundle, Default)]
 pub struct ProgressBarBundle {
     pub progress_bar: ProgressBar,
@@ -46,67 +171,298 @@ pub struct ProgressBarBundle {
     pub computed_visibiltiy: ComputedVisibility,
 }
 
+/// Meshes and materials shared by every bar. Bars are spawned and despawned all the time, so each
+/// one scales the shared meshes to its size instead of adding meshes and materials of its own.
+/// Radial bars swap between pies filled in [`ProgressBarData::RADIAL_STEPS`] steps.
+pub struct ProgressBarData {
+    mesh: Handle<Mesh>,
+    radial_meshes: Vec<Handle<Mesh>>,
+    /// Indexed by whether the bar is paused.
+    background_materials: [Handle<StandardMaterial>; 2],
+    status_materials: HashMap<(ProgressBarFill, bool), Handle<StandardMaterial>>,
+    font: Handle<Font>,
+}
+
+impl FromWorld for ProgressBarData {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let asset_server = world.resource::<AssetServer>();
+        let mut material = |color: Color, depth_bias: f32, paused: bool| {
+            materials.add(StandardMaterial {
+                base_color: if paused { dim(color) } else { color },
+                depth_bias,
+                unlit: true,
+                ..default()
+            })
+        };
+        let background = Color::rgb_u8(50, 50, 50);
+        Self {
+            mesh: meshes.add(Quad::new(Vec2::ONE).into()),
+            radial_meshes: (0..=Self::RADIAL_STEPS)
+                .map(|step| meshes.add(pie(step as f32 / Self::RADIAL_STEPS as f32)))
+                .collect(),
+            background_materials: [
+                material(background, 100.0, false),
+                material(background, 100.0, true),
+            ],
+            status_materials: ProgressBarFill::ALL
+                .into_iter()
+                .flat_map(|fill| [(fill, false), (fill, true)])
+                .map(|(fill, paused)| ((fill, paused), material(fill.color(), 102.0, paused)))
+                .collect(),
+            font: asset_server.load("fonts/DejaVuSans.ttf"),
+        }
+    }
+}
+
+impl ProgressBarData {
+    pub const RADIAL_STEPS: usize = 32;
+
+    fn radial_mesh(&self, percent: f32) -> Handle<Mesh> {
+        let step = (percent * Self::RADIAL_STEPS as f32).round() as usize;
+        self.radial_meshes[step.min(Self::RADIAL_STEPS)].clone()
+    }
+
+    fn status_mesh(&self, bar: &ProgressBar) -> Handle<Mesh> {
+        match bar.style {
+            ProgressBarStyle::Linear => self.mesh.clone(),
+            ProgressBarStyle::Radial => self.radial_mesh(bar.percent()),
+        }
+    }
+
+    fn status_material(&self, bar: &ProgressBar) -> Handle<StandardMaterial> {
+        self.status_materials[&(bar.fill(), bar.paused)].clone()
+    }
+
+    fn background_material(&self, bar: &ProgressBar) -> Handle<StandardMaterial> {
+        self.background_materials[bar.paused as usize].clone()
+    }
+}
+
+fn dim(color: Color) -> Color {
+    let [r, g, b, a] = color.as_rgba_f32();
+    Color::rgba(r * 0.5, g * 0.5, b * 0.5, a)
+}
+
+/// A disc of diameter 1 filled clockwise from the top up to `percent`.
+fn pie(percent: f32) -> Mesh {
+    let segments = (ProgressBarData::RADIAL_STEPS as f32 * percent)
+        .ceil()
+        .max(1.0) as u32;
+    let mut positions = vec![[0.0, 0.0, 0.0]];
+    for i in 0..=segments {
+        let angle = TAU * percent * i as f32 / segments as f32;
+        positions.push([angle.sin() * 0.5, angle.cos() * 0.5, 0.0]);
+    }
+    let uvs: Vec<[f32; 2]> = positions
+        .iter()
+        .map(|[x, y, _]| [x + 0.5, 0.5 - y])
+        .collect();
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+    // clockwise outline, so wind each triangle backwards to face the camera
+    let indices = (1..=segments).flat_map(|i| [0, i + 1, i]).collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
 fn on_spawn_progress_bar(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    progress_bar_data: Res<ProgressBarData>,
     bars: Query<(Entity, &ProgressBar), Added<ProgressBar>>,
 ) {
     for (entity, bar) in &bars {
+        let (background_mesh, background_scale) = match bar.style {
+            ProgressBarStyle::Linear => (
+                progress_bar_data.mesh.clone(),
+                Vec3::new(bar.width, bar.height, 1.0),
+            ),
+            ProgressBarStyle::Radial => (
+                progress_bar_data.radial_mesh(1.0),
+                Vec3::new(bar.width, bar.width, 1.0),
+            ),
+        };
         commands.entity(entity).with_children(|parent| {
-            parent.spawn_bundle(PbrBundle {
-                material: materials.add(StandardMaterial {
-                    base_color: Color::rgb_u8(50, 50, 50),
-                    depth_bias: 100.0,
-                    unlit: true,
+            parent
+                .spawn_bundle(PbrBundle {
+                    material: progress_bar_data.background_material(bar),
+                    mesh: background_mesh,
+                    transform: Transform::from_xyz(0.0, 0.0, ProgressBar::Z)
+                        .with_scale(background_scale),
                     ..default()
-                }),
-                mesh: meshes.add(
-                    Quad {
-                        size: Vec2::new(bar.width, bar.height),
-                        ..default()
-                    }
-                    .into(),
-                ),
-                transform: Transform::from_xyz(0.0, 0.0, ProgressBar::Z),
-                ..default()
-            });
+                })
+                .insert(ProgressBarBackground);
             parent
                 .spawn_bundle(PbrBundle {
-                    material: materials.add(StandardMaterial {
-                        base_color: Color::rgb_u8(150, 150, 150),
-                        depth_bias: 102.0,
-                        unlit: true,
-                        ..default()
-                    }),
-                    transform: Transform::from_xyz(0.0, 0.0, ProgressBar::Z + 0.005),
-                    mesh: meshes.add(
-                        Quad {
-                            size: Vec2::new(
-                                bar.width - bar.padding * 2.0,
-                                bar.height - bar.padding * 2.0,
-                            ),
-                            ..default()
-                        }
-                        .into(),
-                    ),
+                    material: progress_bar_data.status_material(bar),
+                    mesh: progress_bar_data.status_mesh(bar),
+                    transform: status_transform(bar),
                     ..default()
                 })
                 .insert(ProgressBarStatus);
         });
+
+        if bar.label {
+            commands
+                .spawn_bundle(
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: progress_bar_data.font.clone(),
+                            font_size: 14.0,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    }),
+                )
+                .insert(ProgressBarLabel { bar: entity });
+        }
     }
 }
 
+/// Where the status quad of `bar` sits for its current progress. Linear bars fill from the left.
+fn status_transform(bar: &ProgressBar) -> Transform {
+    let inner_width = bar.width - bar.padding * 2.0;
+    match bar.style {
+        ProgressBarStyle::Linear => {
+            let percent = bar.percent();
+            Transform::from_xyz(
+                -inner_width * (1.0 - percent) / 2.0,
+                0.0,
+                ProgressBar::Z + 0.005,
+            )
+            .with_scale(Vec3::new(
+                inner_width * percent,
+                bar.height - bar.padding * 2.0,
+                1.0,
+            ))
+        }
+        ProgressBarStyle::Radial => Transform::from_xyz(0.0, 0.0, ProgressBar::Z + 0.005)
+            .with_scale(Vec3::new(inner_width, inner_width, 1.0)),
+    }
+}
+
+/// Only looks at bars that changed, so idle bars, like the stock of a tile nobody works, cost
+/// nothing.
 fn set_progress_bar(
-    mut statuses: Query<(&Parent, &mut Transform), With<ProgressBarStatus>>,
-    bars: Query<&ProgressBar>,
+    progress_bar_data: Res<ProgressBarData>,
+    mut statuses: Query<
+        (
+            &mut Transform,
+            &mut Handle<Mesh>,
+            &mut Handle<StandardMaterial>,
+        ),
+        With<ProgressBarStatus>,
+    >,
+    mut backgrounds: Query<
+        &mut Handle<StandardMaterial>,
+        (With<ProgressBarBackground>, Without<ProgressBarStatus>),
+    >,
+    bars: Query<(&ProgressBar, &Children), Changed<ProgressBar>>,
+) {
+    for (bar, children) in &bars {
+        for child in children.iter() {
+            if let Ok((mut transform, mut mesh, mut material)) = statuses.get_mut(*child) {
+                let target = status_transform(bar);
+                if *transform != target {
+                    *transform = target;
+                }
+                let target = progress_bar_data.status_mesh(bar);
+                if *mesh != target {
+                    *mesh = target;
+                }
+                let target = progress_bar_data.status_material(bar);
+                if *material != target {
+                    *material = target;
+                }
+            }
+            if let Ok(mut material) = backgrounds.get_mut(*child) {
+                let target = progress_bar_data.background_material(bar);
+                if *material != target {
+                    *material = target;
+                }
+            }
+        }
+    }
+}
+
+/// Moves labels next to their bar on screen and updates their text. Labels of despawned bars are
+/// removed here too, as they live in the UI rather than under the bar.
+fn update_progress_bar_labels(
+    mut commands: Commands,
+    cameras: Query<(&Camera, &GlobalTransform), With<PlayerCamera>>,
+    bars: Query<(&ProgressBar, &GlobalTransform, &ComputedVisibility)>,
+    mut labels: Query<(
+        Entity,
+        &ProgressBarLabel,
+        &mut Text,
+        &mut Style,
+        &mut Visibility,
+    )>,
 ) {
-    for (parent, mut transform) in &mut statuses {
-        if let Ok(bar) = bars.get(parent.get()) {
-            let percent = bar.current / bar.total;
-            transform.scale.x = percent;
-            transform.translation.x = -(bar.width - bar.padding * 2.0) * (1.0 - percent) / 2.0;
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    for (entity, label, mut text, mut style, mut visibility) in &mut labels {
+        let (bar, transform, bar_visibility) = match bars.get(label.bar) {
+            Ok(bar) => bar,
+            Err(_) => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+        let anchor = transform.translation() + Vec3::X * (bar.width / 2.0 + 0.1);
+        let position = camera.world_to_viewport(camera_transform, anchor);
+        let is_visible = bar_visibility.is_visible() && position.is_some();
+        if visibility.is_visible != is_visible {
+            visibility.is_visible = is_visible;
         }
+        if let Some(position) = position {
+            let target = UiRect {
+                left: Val::Px(position.x),
+                bottom: Val::Px(position.y - 7.0),
+                ..default()
+            };
+            // moving a label relayouts the UI, so only move the ones that moved on screen
+            if style.position != target {
+                style.position = target;
+            }
+        }
+
+        let value = match (bar.paused, bar.state, bar.remaining_seconds()) {
+            (true, _, _) => "paused".to_string(),
+            (false, ProgressBarState::Blocked, _) => "blocked".to_string(),
+            (false, ProgressBarState::Working, Some(seconds)) => format!("{:.0}s", seconds.ceil()),
+            (false, ProgressBarState::Working, None) => String::new(),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bars_tick_in_seconds() {
+        let mut bar = ProgressBar {
+            total: 2.0,
+            ..default()
+        };
+        bar.tick(0.5);
+        assert_eq!(bar.current, 0.5);
+        assert_eq!(bar.remaining_seconds(), Some(1.5));
     }
 }
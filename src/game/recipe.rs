@@ -0,0 +1,353 @@
+use std::{borrow::Cow, ops::Index};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use crate::game::{
+    card::{CardType, StackRoots},
+    config::GameConfig,
+};
+
+pub const RECIPES_PATH: &str = "base.recipes.ron";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RecipeId(usize);
+
+/// A combination of cards that, when stacked together, produces new cards after `duration` seconds.
+/// A stack matches a recipe when its cards are exactly the recipe's inputs, in any order.
+#[derive(Deserialize, Clone)]
+pub struct Recipe {
+    pub name: Cow<'static, str>,
+    pub inputs: Vec<RecipeInput>,
+    pub duration: f32,
+    pub outputs: Vec<CardType>,
+    /// Outputs are stacked on top of the kept inputs instead of being dealt next to the stack.
+    #[serde(default)]
+    pub stack_outputs: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RecipeInput {
+    pub card_type: CardType,
+    pub count: usize,
+    /// Consumed cards are removed from the stack when the recipe finishes, kept cards stay.
+    #[serde(default)]
+    pub consumed: bool,
+}
+
+impl Recipe {
+    pub fn new(name: impl Into<Cow<'static, str>>, duration: f32) -> Self {
+        Self {
+            name: name.into(),
+            inputs: Vec::new(),
+            duration,
+            outputs: Vec::new(),
+            stack_outputs: false,
+        }
+    }
+
+    pub fn keep(mut self, card_type: CardType, count: usize) -> Self {
+        self.inputs.push(RecipeInput {
+            card_type,
+            count,
+            consumed: false,
+        });
+        self
+    }
+
+    pub fn consume(mut self, card_type: CardType, count: usize) -> Self {
+        self.inputs.push(RecipeInput {
+            card_type,
+            count,
+            consumed: true,
+        });
+        self
+    }
+
+    pub fn output(mut self, card_type: CardType) -> Self {
+        self.outputs.push(card_type);
+        self
+    }
+
+    pub fn stack_outputs(mut self) -> Self {
+        self.stack_outputs = true;
+        self
+    }
+
+    pub fn input_counts(&self) -> HashMap<CardType, usize> {
+        self.counts(|_| true)
+    }
+
+    pub fn consumed_counts(&self) -> HashMap<CardType, usize> {
+        self.counts(|input| input.consumed)
+    }
+
+    pub fn matches(&self, card_types: &HashMap<CardType, usize>) -> bool {
+        self.input_counts() == *card_types
+    }
+
+    fn counts(&self, filter: impl Fn(&RecipeInput) -> bool) -> HashMap<CardType, usize> {
+        let mut counts = HashMap::new();
+        for input in self.inputs.iter().filter(|input| filter(input)) {
+            *counts.entry(input.card_type.clone()).or_insert(0) += input.count;
+        }
+        counts
+    }
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "0b6f3c52-7a4e-4d7e-8f6a-2e9c1d4b7a13"]
+pub struct RecipeDefinitions(pub Vec<Recipe>);
+
+#[derive(Default)]
+pub struct RecipesLoader;
+
+impl AssetLoader for RecipesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let recipes = ron::de::from_bytes::<RecipeDefinitions>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(recipes));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["recipes.ron"]
+    }
+}
+
+/// The recipes currently in use: the recipes asset, with durations overridden by the
+/// [`GameConfig`]. Rebuilt whenever either changes, see [`update_recipes`].
+pub struct Recipes {
+    handle: Handle<RecipeDefinitions>,
+    recipes: Vec<Recipe>,
+}
+
+impl FromWorld for Recipes {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self {
+            handle: asset_server.load(RECIPES_PATH),
+            recipes: Vec::new(),
+        }
+    }
+}
+
+impl Recipes {
+    pub fn is_loaded(&self) -> bool {
+        !self.recipes.is_empty()
+    }
+
+    /// Replaces the recipes with `definitions`, taking durations from `config` where it sets them.
+    pub fn set(&mut self, definitions: &[Recipe], config: &GameConfig) {
+        self.recipes = definitions
+            .iter()
+            .cloned()
+            .map(|mut recipe| {
+                recipe.duration = config.recipe_duration(&recipe);
+                recipe
+            })
+            .collect();
+    }
+
+    pub fn get(&self, id: RecipeId) -> Option<&Recipe> {
+        self.recipes.get(id.0)
+    }
+
+    /// Returns the first recipe whose inputs exactly match the given card counts.
+    pub fn find(&self, card_types: &HashMap<CardType, usize>) -> Option<RecipeId> {
+        self.recipes
+            .iter()
+            .position(|recipe| recipe.matches(card_types))
+            .map(RecipeId)
+    }
+}
+
+/// Rebuilds the recipes when the recipes asset is (re)loaded, so edits to the asset file show up in
+/// a running game, and when the [`GameConfig`] changes, which the editor does when it reloads a
+/// scenario. Ids may point at other recipes afterwards, so every stack is looked at again.
+pub fn update_recipes(
+    mut events: EventReader<AssetEvent<RecipeDefinitions>>,
+    config: Res<GameConfig>,
+    definitions: Res<Assets<RecipeDefinitions>>,
+    mut recipes: ResMut<Recipes>,
+    mut stack_roots: ResMut<StackRoots>,
+) {
+    let reloaded = events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == recipes.handle
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !reloaded && !config.is_changed() {
+        return;
+    }
+    if let Some(loaded) = definitions.get(&recipes.handle) {
+        recipes.set(&loaded.0, &config);
+        stack_roots.queue_all();
+    }
+}
+
+impl Index<RecipeId> for Recipes {
+    type Output = Recipe;
+
+    fn index(&self, id: RecipeId) -> &Self::Output {
+        &self.recipes[id.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(card_types: &[(CardType, usize)]) -> HashMap<CardType, usize> {
+        card_types.iter().cloned().collect()
+    }
+
+    fn recipes(recipes: Vec<Recipe>) -> Recipes {
+        Recipes {
+            handle: Handle::default(),
+            recipes,
+        }
+    }
+
+    #[test]
+    fn matches_exact_multiset() {
+        let recipe = Recipe::new("test", 1.0)
+            .keep(CardType::VILLAGER, 1)
+            .consume(CardType::LOG, 2);
+        assert!(recipe.matches(&counts(&[(CardType::LOG, 2), (CardType::VILLAGER, 1)])));
+        assert!(!recipe.matches(&counts(&[(CardType::LOG, 1), (CardType::VILLAGER, 1)])));
+        assert!(!recipe.matches(&counts(&[(CardType::LOG, 3), (CardType::VILLAGER, 1)])));
+        assert!(!recipe.matches(&counts(&[
+            (CardType::LOG, 2),
+            (CardType::VILLAGER, 1),
+            (CardType::STONE, 1),
+        ])));
+    }
+
+    #[test]
+    fn inputs_of_the_same_type_add_up() {
+        let recipe = Recipe::new("test", 1.0)
+            .keep(CardType::VILLAGER, 1)
+            .consume(CardType::VILLAGER, 1);
+        assert!(recipe.matches(&counts(&[(CardType::VILLAGER, 2)])));
+    }
+
+    #[test]
+    fn only_consumed_inputs_are_consumed() {
+        let recipe = Recipe::new("test", 1.0)
+            .keep(CardType::VILLAGER, 2)
+            .consume(CardType::WHEAT, 1)
+            .consume(CardType::LOG, 2);
+        assert_eq!(
+            recipe.input_counts(),
+            counts(&[
+                (CardType::VILLAGER, 2),
+                (CardType::WHEAT, 1),
+                (CardType::LOG, 2),
+            ])
+        );
+        assert_eq!(
+            recipe.consumed_counts(),
+            counts(&[(CardType::WHEAT, 1), (CardType::LOG, 2)])
+        );
+    }
+
+    #[test]
+    fn find_returns_first_match() {
+        let recipes = recipes(vec![
+            Recipe::new("logs", 1.0).consume(CardType::LOG, 2),
+            Recipe::new("breed", 1.0).keep(CardType::VILLAGER, 2),
+            Recipe::new("breed again", 1.0).keep(CardType::VILLAGER, 2),
+        ]);
+        let id = recipes.find(&counts(&[(CardType::VILLAGER, 2)])).unwrap();
+        assert_eq!(recipes[id].name, "breed");
+        assert_eq!(
+            recipes.get(id).map(|recipe| recipe.name.as_ref()),
+            Some("breed")
+        );
+    }
+
+    #[test]
+    fn find_without_match() {
+        let recipes = recipes(vec![Recipe::new("breed", 1.0).keep(CardType::VILLAGER, 2)]);
+        assert_eq!(recipes.find(&counts(&[(CardType::VILLAGER, 1)])), None);
+        assert_eq!(recipes.find(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn outputs_are_dealt_unless_stacked() {
+        let dealt = Recipe::new("dealt", 1.0)
+            .consume(CardType::WHEAT, 1)
+            .output(CardType::BERRIES)
+            .output(CardType::BERRIES);
+        assert!(!dealt.stack_outputs);
+        assert_eq!(dealt.outputs, vec![CardType::BERRIES, CardType::BERRIES]);
+
+        let stacked = Recipe::new("stacked", 1.0)
+            .keep(CardType::VILLAGER, 1)
+            .output(CardType::LOG)
+            .stack_outputs();
+        assert!(stacked.stack_outputs);
+        assert_eq!(stacked.outputs, vec![CardType::LOG]);
+    }
+
+    #[test]
+    fn recipes_read_from_ron() {
+        let definitions: RecipeDefinitions = ron::de::from_str(
+            r#"([
+                (
+                    name: "breed",
+                    duration: 5.0,
+                    inputs: [(card_type: "villager", count: 2)],
+                    outputs: ["villager"],
+                ),
+                (
+                    name: "campfire",
+                    duration: 2.0,
+                    inputs: [(card_type: "log", count: 1, consumed: true)],
+                    outputs: ["stone"],
+                    stack_outputs: true,
+                ),
+            ])"#,
+        )
+        .unwrap();
+        let [breed, campfire] = &definitions.0[..] else {
+            panic!("expected two recipes");
+        };
+        assert_eq!(breed.input_counts(), counts(&[(CardType::VILLAGER, 2)]));
+        assert!(breed.consumed_counts().is_empty());
+        assert!(!breed.stack_outputs);
+        assert_eq!(campfire.consumed_counts(), counts(&[(CardType::LOG, 1)]));
+        assert_eq!(campfire.outputs, vec![CardType::STONE]);
+        assert!(campfire.stack_outputs);
+    }
+
+    #[test]
+    fn config_overrides_durations() {
+        let definitions = vec![
+            Recipe::new("breed", 5.0).keep(CardType::VILLAGER, 2),
+            Recipe::new("logs", 3.0).consume(CardType::LOG, 2),
+        ];
+        let mut config = GameConfig::default();
+        config.recipe_durations.insert("breed".to_string(), 1.0);
+        let mut recipes = recipes(Vec::new());
+        recipes.set(&definitions, &config);
+        let durations: Vec<f32> = recipes
+            .recipes
+            .iter()
+            .map(|recipe| recipe.duration)
+            .collect();
+        assert_eq!(durations, vec![1.0, 3.0]);
+    }
+}
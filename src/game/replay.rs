@@ -0,0 +1,233 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{app::AppExit, prelude::*};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    action::{PlayerAction, SendPlayerActions},
+    config::GameConfig,
+    simulation::SimulationClock,
+    state::{playing, GameState},
+};
+
+pub const REPLAY_VERSION: u32 = 2;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            replay_actions
+                .with_run_criteria(playing)
+                .label(SendPlayerActions),
+        )
+        .add_system(record_actions.after(SendPlayerActions))
+        .add_system_to_stage(CoreStage::Last, flush_recording)
+        .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(end_on_restart));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReplayFile {
+    pub version: u32,
+    /// The scenario the actions were taken in. Version 1 replays didn't record it.
+    #[serde(default)]
+    pub config: Option<GameConfig>,
+    pub actions: Vec<RecordedAction>,
+}
+
+/// An action and the [`SimulationClock::tick`] it was taken at.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct RecordedAction {
+    pub tick: u64,
+    pub action: PlayerAction,
+}
+
+impl ReplayFile {
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        let replay: ReplayFile = ron::de::from_bytes(&fs::read(path)?)?;
+        if replay.version > REPLAY_VERSION {
+            anyhow::bail!(
+                "unsupported replay version {} (expected at most {})",
+                replay.version,
+                REPLAY_VERSION
+            );
+        }
+        Ok(replay)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), anyhow::Error> {
+        fs::write(path, ron::ser::to_string_pretty(self, PrettyConfig::new())?)?;
+        Ok(())
+    }
+}
+
+/// Feeds recorded actions back in place of the mouse and keyboard. Live input resumes once
+/// every action has been played, or when a new game is started.
+pub struct Replay {
+    actions: VecDeque<RecordedAction>,
+}
+
+impl Replay {
+    /// Reads a replay to be played on `config`. Replaying actions on another board than the one
+    /// they were recorded on would play out a different game, so that's an error. Version 1
+    /// replays don't say which board they're from and are taken on trust.
+    pub fn read(path: &Path, config: &GameConfig) -> Result<Self, anyhow::Error> {
+        let replay = ReplayFile::read(path)?;
+        if replay
+            .config
+            .as_ref()
+            .is_some_and(|recorded| recorded != config)
+        {
+            anyhow::bail!("it was recorded on a different scenario");
+        }
+        Ok(Self {
+            actions: replay.actions.into(),
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+/// Writes every [`PlayerAction`] to `path`. The file is rewritten every few seconds and on exit,
+/// so a crash loses at most the last few actions. Ticks start over with every game, so only the
+/// first game is recorded.
+pub struct Recorder {
+    path: PathBuf,
+    config: GameConfig,
+    actions: Vec<RecordedAction>,
+    dirty: bool,
+}
+
+impl Recorder {
+    pub const FLUSH_INTERVAL: f32 = 5.0;
+
+    /// Records a game played on `config`.
+    pub fn new(path: impl Into<PathBuf>, config: GameConfig) -> Self {
+        Self {
+            path: path.into(),
+            config,
+            actions: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    pub fn write(&mut self) -> Result<(), anyhow::Error> {
+        ReplayFile {
+            version: REPLAY_VERSION,
+            config: Some(self.config.clone()),
+            actions: self.actions.clone(),
+        }
+        .write(&self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+pub fn replay_actions(
+    replay: Option<ResMut<Replay>>,
+    mut clock: ResMut<SimulationClock>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    let mut replay = match replay {
+        Some(replay) => replay,
+        None => return,
+    };
+
+    while let Some(recorded) = replay.actions.front() {
+        if recorded.tick > clock.tick() {
+            break;
+        }
+        actions.send(recorded.action);
+        replay.actions.pop_front();
+    }
+
+    // don't let the simulation run past the next action before it has been sent
+    let next_tick = replay.actions.front().map(|recorded| recorded.tick);
+    clock.hold_at(next_tick);
+}
+
+fn record_actions(
+    recorder: Option<ResMut<Recorder>>,
+    clock: Res<SimulationClock>,
+    mut actions: EventReader<PlayerAction>,
+) {
+    let mut recorder = match recorder {
+        Some(recorder) => recorder,
+        None => return,
+    };
+
+    for action in actions.iter() {
+        recorder.actions.push(RecordedAction {
+            tick: clock.tick(),
+            action: *action,
+        });
+        recorder.dirty = true;
+    }
+}
+
+fn flush_recording(
+    mut timer: Local<Option<Timer>>,
+    time: Res<Time>,
+    recorder: Option<ResMut<Recorder>>,
+    mut exit: EventReader<AppExit>,
+) {
+    let mut recorder = match recorder {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let timer = timer.get_or_insert_with(|| Timer::from_seconds(Recorder::FLUSH_INTERVAL, true));
+    timer.tick(time.delta());
+
+    if recorder.dirty && (timer.just_finished() || exit.iter().last().is_some()) {
+        match recorder.write() {
+            Ok(()) => {}
+            Err(err) => error!(
+                "failed to write replay to {}: {}",
+                recorder.path.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// Recordings and replays cover a single game: the clock starts over at tick 0 for the next one,
+/// so its actions couldn't be told apart from the first game's. The recording is written out one
+/// last time and both are dropped when another game starts.
+fn end_on_restart(
+    mut commands: Commands,
+    mut started: Local<bool>,
+    recorder: Option<ResMut<Recorder>>,
+    replay: Option<Res<Replay>>,
+) {
+    if !*started {
+        *started = true;
+        return;
+    }
+
+    if let Some(mut recorder) = recorder {
+        match recorder.write() {
+            Ok(()) => info!(
+                "game restarted, stopped recording to {}",
+                recorder.path.display()
+            ),
+            Err(err) => error!(
+                "failed to write replay to {}: {}",
+                recorder.path.display(),
+                err
+            ),
+        }
+        commands.remove_resource::<Recorder>();
+    }
+    // the clock was reset along with any hold the replay had on it
+    if replay.is_some() {
+        commands.remove_resource::<Replay>();
+    }
+}
@@ -0,0 +1,547 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    card::{
+        spawn_recipe_progress_bar, Card, CardBundle, CardInfo, CardType, SelectedCard, StackRoots,
+        StackType,
+    },
+    card_definition::CardRegistry,
+    combat::{Dying, Engagement},
+    config::GameConfig,
+    progress_bar::ProgressBar,
+    recipe::Recipes,
+    simulation::{SimulationClock, TimeScale},
+    state::playing,
+    tile::{
+        EnemyTileSpawner, EnemyTileSpawnerState, HoveredTile, Tile, TileBundle, TileGrid,
+        TileGridLocation, TileKind,
+    },
+};
+
+pub const SAVE_VERSION: u32 = 6;
+pub const DEFAULT_SAVE_PATH: &str = "card_combinator.save.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveRequest>()
+            .add_event::<LoadRequest>()
+            .add_system(save_game)
+            .add_system(load_game);
+    }
+}
+
+pub struct SaveKeybindingsPlugin;
+
+impl Plugin for SaveKeybindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            save_load_input
+                .with_run_criteria(playing)
+                .before(save_game)
+                .before(load_game),
+        );
+    }
+}
+
+pub struct SaveRequest(pub PathBuf);
+
+pub struct LoadRequest(pub PathBuf);
+
+/// Marks cards spawned from a save, which keep their saved stats instead of their definition's.
+#[derive(Component)]
+pub struct RestoredCard;
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub cards: Vec<SavedCard>,
+    pub tiles: Vec<SavedTile>,
+    pub stacks: Vec<SavedStack>,
+    /// The simulation tick the game was saved on. Saves before version 6 start over from 0.
+    #[serde(default)]
+    pub tick: u64,
+    /// Saves before version 6 don't have this, the spawner starts over for them.
+    #[serde(default)]
+    pub enemy_tile_spawner: Option<EnemyTileSpawnerState>,
+}
+
+/// Entity references are stored as indices into [`SaveGame::cards`] and [`SaveGame::tiles`].
+#[derive(Serialize, Deserialize)]
+pub struct SavedCard {
+    pub info: CardInfo,
+    pub position: [f32; 2],
+    pub stack_parent: Option<usize>,
+    pub stack_child: Option<usize>,
+    pub slotted_in_tile: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedTile {
+    pub location: [i32; 2],
+    pub kind: SavedTileKind,
+    pub progress: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum SavedTileKind {
+    Producer {
+        kind: TileKind,
+        /// By slot.
+        slotted_villagers: Vec<Option<usize>>,
+        stock: u32,
+        regrowth: f32,
+    },
+    Enemies {
+        squad: Option<usize>,
+        damage_taken: u32,
+        assault: f32,
+    },
+}
+
+/// Progress of the recipe running on the stack rooted at `root`.
+#[derive(Serialize, Deserialize)]
+pub struct SavedStack {
+    pub root: usize,
+    pub progress: f32,
+}
+
+/// Just enough of a save to tell which format the rest is in.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// Saves from before version 5, when enemy tiles had no state.
+#[derive(Deserialize)]
+struct LegacySaveGame {
+    cards: Vec<SavedCard>,
+    tiles: Vec<LegacySavedTile>,
+    stacks: Vec<SavedStack>,
+}
+
+#[derive(Deserialize)]
+struct LegacySavedTile {
+    location: [i32; 2],
+    kind: LegacySavedTileKind,
+    progress: Option<f32>,
+}
+
+#[derive(Deserialize)]
+enum LegacySavedTileKind {
+    /// Version 1, when woods were the only producing tile.
+    Woods {
+        slotted_villager: Option<usize>,
+    },
+    Producer {
+        kind: TileKind,
+        /// Version 2, when tiles had a single slot.
+        #[serde(default)]
+        slotted_villager: Option<usize>,
+        #[serde(default)]
+        slotted_villagers: Vec<Option<usize>>,
+        /// Missing before version 4, when tiles never ran out.
+        #[serde(default)]
+        stock: Option<u32>,
+        #[serde(default)]
+        regrowth: f32,
+    },
+    Enemies,
+}
+
+impl LegacySaveGame {
+    fn migrate(self) -> SaveGame {
+        let tiles = self
+            .tiles
+            .into_iter()
+            .map(|tile| {
+                let producer = |kind: TileKind, slotted_villagers, stock: Option<u32>, regrowth| {
+                    SavedTileKind::Producer {
+                        kind,
+                        slotted_villagers,
+                        stock: stock.unwrap_or_else(|| {
+                            kind.production().map_or(0, |production| production.stock)
+                        }),
+                        regrowth,
+                    }
+                };
+                let kind = match tile.kind {
+                    LegacySavedTileKind::Woods { slotted_villager } => {
+                        producer(TileKind::Woods, vec![slotted_villager], None, 0.0)
+                    }
+                    LegacySavedTileKind::Producer {
+                        kind,
+                        slotted_villager,
+                        mut slotted_villagers,
+                        stock,
+                        regrowth,
+                    } => {
+                        if slotted_villagers.is_empty() {
+                            slotted_villagers.push(slotted_villager);
+                        }
+                        producer(kind, slotted_villagers, stock, regrowth)
+                    }
+                    LegacySavedTileKind::Enemies => SavedTileKind::Enemies {
+                        squad: None,
+                        damage_taken: 0,
+                        assault: 0.0,
+                    },
+                };
+                SavedTile {
+                    location: tile.location,
+                    kind,
+                    progress: tile.progress,
+                }
+            })
+            .collect();
+        SaveGame {
+            version: SAVE_VERSION,
+            cards: self.cards,
+            tiles,
+            stacks: self.stacks,
+            tick: 0,
+            enemy_tile_spawner: None,
+        }
+    }
+}
+
+impl SaveGame {
+    /// Reads saves of any version up to [`SAVE_VERSION`], bringing older ones up to date. Card
+    /// stats that older versions didn't save are taken from the definitions in `registry`.
+    pub fn read(path: &Path, registry: &CardRegistry) -> Result<Self, anyhow::Error> {
+        let bytes = fs::read(path)?;
+        let SaveHeader { version } = ron::de::from_bytes(&bytes)?;
+        let mut save = match version {
+            5..=SAVE_VERSION => ron::de::from_bytes(&bytes)?,
+            1..=4 => ron::de::from_bytes::<LegacySaveGame>(&bytes)?.migrate(),
+            _ => anyhow::bail!(
+                "unsupported save version {} (expected at most {})",
+                version,
+                SAVE_VERSION
+            ),
+        };
+        // version 3 added work speed, and attack speed came late in version 1
+        if version < 3 {
+            for card in &mut save.cards {
+                if let Some(definition) = registry.get(&card.info.card_type) {
+                    let stats = definition.stats();
+                    card.info.stats.work_speed = stats.work_speed;
+                    if card.info.stats.attack_speed == 0.0 {
+                        card.info.stats.attack_speed = stats.attack_speed;
+                    }
+                }
+            }
+        }
+        save.version = SAVE_VERSION;
+        Ok(save)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), anyhow::Error> {
+        fs::write(path, ron::ser::to_string_pretty(self, PrettyConfig::new())?)?;
+        Ok(())
+    }
+
+    fn spawn(
+        &self,
+        commands: &mut Commands,
+        recipes: &Recipes,
+        config: &GameConfig,
+        stack_roots: &mut StackRoots,
+    ) {
+        // reserve every entity up front so links can be resolved in a single pass
+        let card_entities: Vec<Entity> = self.cards.iter().map(|_| commands.spawn().id()).collect();
+        let tile_entities: Vec<Entity> = self.tiles.iter().map(|_| commands.spawn().id()).collect();
+        let card_entity = |i: usize| card_entities.get(i).copied();
+        let tile_entity = |i: usize| tile_entities.get(i).copied();
+
+        for (saved, entity) in self.tiles.iter().zip(&tile_entities) {
+            let mut tile = match &saved.kind {
+                SavedTileKind::Producer {
+                    kind,
+                    slotted_villagers,
+                    stock,
+                    regrowth,
+                } => {
+                    let mut slots = [None; Tile::MAX_SLOTS];
+                    for (slot, villager) in slots.iter_mut().zip(slotted_villagers) {
+                        *slot = villager.and_then(card_entity);
+                    }
+                    Tile::Producer {
+                        kind: *kind,
+                        slotted_villagers: slots,
+                        progress_bar: None,
+                        stock: *stock,
+                        regrowth: *regrowth,
+                        status_bar: None,
+                    }
+                }
+                SavedTileKind::Enemies {
+                    squad,
+                    damage_taken,
+                    assault,
+                } => Tile::Enemies {
+                    progress_bar: None,
+                    squad: squad.and_then(card_entity),
+                    damage_taken: *damage_taken,
+                    assault: *assault,
+                    status_bar: None,
+                },
+            };
+            if let Some(progress) = saved.progress {
+                let bar = tile.progress_bar(progress, config);
+                let mut bar_entity = None;
+                commands.entity(*entity).with_children(|parent| {
+                    bar_entity = Some(parent.spawn_bundle(bar).id());
+                });
+                match &mut tile {
+                    Tile::Producer { progress_bar, .. } | Tile::Enemies { progress_bar, .. } => {
+                        *progress_bar = bar_entity;
+                    }
+                }
+            }
+            commands.entity(*entity).insert_bundle(TileBundle {
+                tile,
+                tile_grid_location: TileGridLocation(IVec2::from(saved.location)),
+                ..default()
+            });
+        }
+
+        for (saved, entity) in self.cards.iter().zip(&card_entities) {
+            commands
+                .entity(*entity)
+                .insert_bundle(CardBundle {
+                    card: Card {
+                        info: saved.info.clone(),
+                        stack_parent: saved.stack_parent.and_then(card_entity),
+                        stack_child: saved.stack_child.and_then(card_entity),
+                        slotted_in_tile: saved.slotted_in_tile.and_then(tile_entity),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(saved.position[0], saved.position[1], 0.0),
+                    ..default()
+                })
+                .insert(RestoredCard);
+        }
+
+        let progress: HashMap<usize, f32> = self
+            .stacks
+            .iter()
+            .map(|stack| (stack.root, stack.progress))
+            .collect();
+        for (i, saved) in self.cards.iter().enumerate() {
+            if saved.stack_parent.is_some() || saved.stack_child.is_none() {
+                continue;
+            }
+            let root = card_entities[i];
+            let recipe = recipes.find(&self.stack_card_types(i));
+            match (recipe, progress.get(&i)) {
+                (Some(recipe), Some(progress)) => {
+                    let progress_bar = spawn_recipe_progress_bar(
+                        commands,
+                        root,
+                        recipes[recipe].duration,
+                        *progress,
+                    );
+                    stack_roots.insert(
+                        root,
+                        StackType::Recipe {
+                            recipe,
+                            progress_bar,
+                        },
+                    );
+                }
+                _ => {
+                    stack_roots.insert(root, StackType::Pending);
+                    stack_roots.queue_recomputation(root);
+                }
+            }
+        }
+    }
+
+    fn stack_card_types(&self, root: usize) -> HashMap<CardType, usize> {
+        let mut card_types = HashMap::new();
+        let mut current = Some(root);
+        // bounded by the card count so a malformed save can't loop forever
+        for _ in 0..self.cards.len() {
+            let card = match current.and_then(|i| self.cards.get(i)) {
+                Some(card) => card,
+                None => break,
+            };
+            *card_types.entry(card.info.card_type.clone()).or_insert(0) += 1;
+            current = card.stack_child;
+        }
+        card_types
+    }
+}
+
+fn save_load_input(
+    input: Res<Input<KeyCode>>,
+    mut save_requests: EventWriter<SaveRequest>,
+    mut load_requests: EventWriter<LoadRequest>,
+) {
+    if input.just_pressed(KeyCode::F5) {
+        save_requests.send(SaveRequest(DEFAULT_SAVE_PATH.into()));
+    }
+    if input.just_pressed(KeyCode::F9) {
+        load_requests.send(LoadRequest(DEFAULT_SAVE_PATH.into()));
+    }
+}
+
+fn save_game(
+    mut requests: EventReader<SaveRequest>,
+    clock: Res<SimulationClock>,
+    enemy_tile_spawner: Res<EnemyTileSpawner>,
+    stack_roots: Res<StackRoots>,
+    cards: Query<(Entity, &Card, &Transform), Without<Dying>>,
+    tiles: Query<(Entity, &Tile, &TileGridLocation)>,
+    progress_bars: Query<&ProgressBar>,
+) {
+    for SaveRequest(path) in requests.iter() {
+        // cards killed this frame don't have `Dying` yet. Links to skipped cards are dropped
+        // along with them, as their index can't be found.
+        let cards: Vec<_> = cards
+            .iter()
+            .filter(|(_, card, _)| card.info.stats.max_health == 0 || card.info.stats.health > 0)
+            .collect();
+        let tiles: Vec<_> = tiles.iter().collect();
+        let card_indices: HashMap<Entity, usize> = cards
+            .iter()
+            .enumerate()
+            .map(|(i, (entity, _, _))| (*entity, i))
+            .collect();
+        let tile_indices: HashMap<Entity, usize> = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, (entity, _, _))| (*entity, i))
+            .collect();
+        let card_index = |entity: Entity| card_indices.get(&entity).copied();
+        let tile_index = |entity: Entity| tile_indices.get(&entity).copied();
+        let progress = |bar: Option<Entity>| {
+            bar.and_then(|bar| progress_bars.get(bar).ok())
+                .map(|bar| bar.current)
+        };
+
+        let save = SaveGame {
+            version: SAVE_VERSION,
+            cards: cards
+                .iter()
+                .map(|(_, card, transform)| SavedCard {
+                    info: card.info.clone(),
+                    position: [transform.translation.x, transform.translation.y],
+                    stack_parent: card.stack_parent.and_then(card_index),
+                    stack_child: card.stack_child.and_then(card_index),
+                    slotted_in_tile: card.slotted_in_tile.and_then(tile_index),
+                })
+                .collect(),
+            tiles: tiles
+                .iter()
+                .map(|(_, tile, location)| match tile {
+                    Tile::Producer {
+                        kind,
+                        progress_bar,
+                        stock,
+                        regrowth,
+                        ..
+                    } => SavedTile {
+                        location: location.to_array(),
+                        kind: SavedTileKind::Producer {
+                            kind: *kind,
+                            slotted_villagers: tile
+                                .slotted_villagers()
+                                .iter()
+                                .map(|villager| villager.and_then(card_index))
+                                .collect(),
+                            stock: *stock,
+                            regrowth: *regrowth,
+                        },
+                        progress: progress(*progress_bar),
+                    },
+                    Tile::Enemies {
+                        progress_bar,
+                        squad,
+                        damage_taken,
+                        assault,
+                        ..
+                    } => SavedTile {
+                        location: location.to_array(),
+                        kind: SavedTileKind::Enemies {
+                            squad: squad.and_then(card_index),
+                            damage_taken: *damage_taken,
+                            assault: *assault,
+                        },
+                        progress: progress(*progress_bar),
+                    },
+                })
+                .collect(),
+            stacks: cards
+                .iter()
+                .filter_map(|(entity, _, _)| match stack_roots.get(*entity) {
+                    Some(StackType::Recipe { progress_bar, .. }) => Some(SavedStack {
+                        root: card_index(*entity)?,
+                        progress: progress(Some(*progress_bar))?,
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            tick: clock.tick(),
+            enemy_tile_spawner: Some(enemy_tile_spawner.state()),
+        };
+
+        match save.write(path) {
+            Ok(()) => info!("saved board to {}", path.display()),
+            Err(err) => error!("failed to save board to {}: {}", path.display(), err),
+        }
+    }
+}
+
+fn load_game(
+    mut commands: Commands,
+    mut requests: EventReader<LoadRequest>,
+    recipes: Res<Recipes>,
+    registry: Res<CardRegistry>,
+    config: Res<GameConfig>,
+    mut clock: ResMut<SimulationClock>,
+    mut time_scale: ResMut<TimeScale>,
+    mut enemy_tile_spawner: ResMut<EnemyTileSpawner>,
+    mut stack_roots: ResMut<StackRoots>,
+    mut tile_grid: ResMut<TileGrid>,
+    mut selected_card: ResMut<SelectedCard>,
+    mut hovered_tile: ResMut<HoveredTile>,
+    board: Query<Entity, Or<(With<Card>, With<Tile>, With<Engagement>)>>,
+) {
+    for LoadRequest(path) in requests.iter() {
+        let save = match SaveGame::read(path, &registry) {
+            Ok(save) => save,
+            Err(err) => {
+                error!("failed to load board from {}: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        for entity in &board {
+            commands.entity(entity).despawn_recursive();
+        }
+        stack_roots.clear();
+        tile_grid.clear();
+        *selected_card = SelectedCard::None;
+        hovered_tile.0 = None;
+        // a loaded game runs at normal speed, like a new one
+        clock.set_tick(save.tick);
+        *time_scale = TimeScale::default();
+        match save.enemy_tile_spawner {
+            Some(state) => enemy_tile_spawner.restore(state),
+            None => enemy_tile_spawner.reset(),
+        }
+
+        save.spawn(&mut commands, &recipes, &config, &mut stack_roots);
+        info!("loaded board from {}", path.display());
+    }
+}
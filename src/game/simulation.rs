@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use crate::game::state::playing;
+
+pub const SIMULATION_STEP: f64 = 1.0 / 60.0;
+/// The most steps run in a single frame. Time beyond that is dropped rather than caught up on
+/// later, so a long frame slows the game down for a moment instead of making every following
+/// frame longer still.
+pub const MAX_STEPS_PER_FRAME: u32 = 30;
+
+/// Runs after [`CoreStage::Update`] zero or more times per frame, once for every
+/// [`SIMULATION_STEP`] of real time that has passed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct SimulationStage;
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimulationClock>()
+            .init_resource::<TimeScale>()
+            .add_stage_after(
+                CoreStage::Update,
+                SimulationStage,
+                SystemStage::parallel().with_run_criteria(run_simulation_step),
+            );
+    }
+}
+
+/// Gameplay time. Advances in fixed steps, independent of the frame rate, so the same inputs
+/// always play out the same way. Systems in [`SimulationStage`] should use this instead of
+/// [`Time`].
+pub struct SimulationClock {
+    step: f64,
+    tick: u64,
+    accumulator: f64,
+    looping: bool,
+    running: bool,
+    hold: Option<u64>,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            step: SIMULATION_STEP,
+            tick: 0,
+            accumulator: 0.0,
+            looping: false,
+            running: false,
+            hold: None,
+        }
+    }
+}
+
+impl SimulationClock {
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f64(self.step)
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.step as f32
+    }
+
+    /// The number of steps simulated so far. While a step runs, this is the index of that step.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.tick as f64 * self.step
+    }
+
+    /// Jumps to `tick`, for loading a saved game. Time accumulated towards the next step is
+    /// dropped.
+    pub fn set_tick(&mut self, tick: u64) {
+        self.tick = tick;
+        self.accumulator = 0.0;
+    }
+
+    /// Starts stepping. The clock stays at tick 0 until the board has been dealt, so ticks line
+    /// up between runs no matter how long loading took.
+    pub fn start(&mut self) {
+        self.running = true;
+    }
+
+    /// Stops stepping without losing the current tick. Time spent stopped is not caught up on.
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Stops stepping once `tick` is reached, until the hold is moved or cleared. Real time keeps
+    /// accumulating, up to [`MAX_STEPS_PER_FRAME`] steps' worth, so the simulation catches up
+    /// afterwards.
+    pub fn hold_at(&mut self, tick: Option<u64>) {
+        self.hold = tick;
+    }
+}
+
+/// How many seconds of gameplay pass per second of real time. Scaling the time fed to the
+/// [`SimulationClock`], rather than its step, keeps every step the same length, so recipes,
+/// tiles, enemies and combat play out exactly the same at any speed. At 0 the simulation is
+/// paused but cards can still be moved and stacked.
+#[derive(Deref, DerefMut)]
+pub struct TimeScale(pub f64);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl TimeScale {
+    pub const SPEEDS: [f64; 3] = [1.0, 2.0, 4.0];
+
+    pub fn is_paused(&self) -> bool {
+        self.0 == 0.0
+    }
+}
+
+fn run_simulation_step(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    mut clock: ResMut<SimulationClock>,
+) -> ShouldRun {
+    if clock.looping {
+        // the previous step just finished
+        clock.tick += 1;
+    } else {
+        let max_accumulator = clock.step * MAX_STEPS_PER_FRAME as f64;
+        clock.accumulator =
+            (clock.accumulator + time.delta_seconds_f64() * time_scale.0).min(max_accumulator);
+    }
+
+    if !clock.running {
+        clock.accumulator = 0.0;
+        clock.looping = false;
+        return ShouldRun::No;
+    }
+
+    let step = clock.step;
+    let held = matches!(clock.hold, Some(hold) if clock.tick >= hold);
+    if clock.accumulator >= step && !held {
+        clock.accumulator -= step;
+        clock.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        clock.looping = false;
+        ShouldRun::No
+    }
+}
+
+/// Space pauses and resumes the simulation, 1, 2 and 3 pick one of [`TimeScale::SPEEDS`]. The
+/// current speed is shown in the top right corner.
+pub struct TimeScaleControlsPlugin;
+
+impl Plugin for TimeScaleControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_time_scale_indicator)
+            .add_system(time_scale_input.with_run_criteria(playing))
+            .add_system(update_time_scale_indicator.after(time_scale_input));
+    }
+}
+
+#[derive(Component)]
+struct TimeScaleIndicator;
+
+fn time_scale_input(
+    mut speed_before_pause: Local<Option<f64>>,
+    input: Res<Input<KeyCode>>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        if time_scale.is_paused() {
+            time_scale.0 = speed_before_pause.take().unwrap_or(1.0);
+        } else {
+            *speed_before_pause = Some(time_scale.0);
+            time_scale.0 = 0.0;
+        }
+    }
+    let keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3];
+    for (key, speed) in keys.into_iter().zip(TimeScale::SPEEDS) {
+        if input.just_pressed(key) {
+            time_scale.0 = speed;
+        }
+    }
+}
+
+fn spawn_time_scale_indicator(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/DejaVuSans.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(TimeScaleIndicator);
+}
+
+fn update_time_scale_indicator(
+    time_scale: Res<TimeScale>,
+    mut indicators: Query<&mut Text, With<TimeScaleIndicator>>,
+) {
+    if !time_scale.is_changed() {
+        return;
+    }
+    for mut text in &mut indicators {
+        text.sections[0].value = if time_scale.is_paused() {
+            "Paused (Space)".to_string()
+        } else {
+            format!("{}x", time_scale.0)
+        };
+    }
+}
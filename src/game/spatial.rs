@@ -0,0 +1,236 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::game::{
+    card::{evaluate_stacks, Card},
+    combat::hunt_villagers,
+    simulation::SimulationStage,
+    tile::evaluate_tiles,
+};
+
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        // once before the simulation steps, for what moved during Update, and once at the end of
+        // the frame, which is the last chance to see the frame's despawns
+        app.init_resource::<SpatialIndex>()
+            .add_system_to_stage(
+                SimulationStage,
+                index_cards
+                    .before(evaluate_stacks)
+                    .before(evaluate_tiles)
+                    .before(hunt_villagers),
+            )
+            .add_system_to_stage(CoreStage::Last, index_cards);
+    }
+}
+
+/// Cards bucketed by position into square cells, for asking which cards are near a point without
+/// looking at every card. Kept in sync with card [`Transform`]s by [`index_cards`]; cards spawned
+/// by commands can be added right away with [`SpatialIndex::insert`] so later lookups in the same
+/// step see them.
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Entity>>,
+    positions: HashMap<Entity, Vec3>,
+    /// Grows with the cells in use and never shrinks, bounds how far [`Self::nearest`] searches.
+    min_cell: IVec2,
+    max_cell: IVec2,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(Self::CELL_SIZE)
+    }
+}
+
+impl SpatialIndex {
+    /// A bit over a card's height, so the cards overlapping a point are always within one cell of
+    /// it.
+    pub const CELL_SIZE: f32 = 1.5;
+
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+            min_cell: IVec2::ZERO,
+            max_cell: IVec2::ZERO,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn position(&self, entity: Entity) -> Option<Vec3> {
+        self.positions.get(&entity).copied()
+    }
+
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Adds `entity` at `position`, or moves it there if it's already in the index.
+    pub fn insert(&mut self, entity: Entity, position: Vec3) {
+        let cell = self.cell(position.truncate());
+        if let Some(old) = self.positions.insert(entity, position) {
+            let old_cell = self.cell(old.truncate());
+            if old_cell == cell {
+                return;
+            }
+            self.remove_from_cell(entity, old_cell);
+        }
+        self.cells.entry(cell).or_default().push(entity);
+        if self.positions.len() == 1 {
+            self.min_cell = cell;
+            self.max_cell = cell;
+        } else {
+            self.min_cell = self.min_cell.min(cell);
+            self.max_cell = self.max_cell.max(cell);
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(old) = self.positions.remove(&entity) {
+            let old_cell = self.cell(old.truncate());
+            self.remove_from_cell(entity, old_cell);
+        }
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, cell: IVec2) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.retain(|other| *other != entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+        self.min_cell = IVec2::ZERO;
+        self.max_cell = IVec2::ZERO;
+    }
+
+    /// Every entity within `radius` of `center`, with its position.
+    pub fn within(&self, center: Vec2, radius: f32) -> impl Iterator<Item = (Entity, Vec3)> + '_ {
+        let min = self.cell(center - Vec2::splat(radius));
+        let max = self.cell(center + Vec2::splat(radius));
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(|entity| (*entity, self.positions[entity]))
+            .filter(move |(_, position)| position.truncate().distance(center) <= radius)
+    }
+
+    /// The closest entity to `center` that passes `filter`. Searches outwards ring by ring, so
+    /// only cells that could still hold something closer are looked at. Ties go to the lowest
+    /// entity id, so the result doesn't depend on insertion order.
+    pub fn nearest(
+        &self,
+        center: Vec2,
+        mut filter: impl FnMut(Entity) -> bool,
+    ) -> Option<(Entity, Vec3)> {
+        let center_cell = self.cell(center);
+        let max_ring = (self.min_cell - center_cell)
+            .abs()
+            .max((self.max_cell - center_cell).abs())
+            .max_element();
+        let mut best: Option<(Entity, Vec3, f32)> = None;
+        for ring in 0..=max_ring {
+            for cell in ring_cells(center_cell, ring) {
+                for entity in self.cells.get(&cell).into_iter().flatten() {
+                    let position = self.positions[entity];
+                    let distance = position.truncate().distance_squared(center);
+                    let closer = best.is_none_or(|(best_entity, _, best_distance)| {
+                        distance < best_distance
+                            || (distance == best_distance && entity.id() < best_entity.id())
+                    });
+                    if closer && filter(*entity) {
+                        best = Some((*entity, position, distance));
+                    }
+                }
+            }
+            // anything in the next ring is at least `ring` cells away
+            let reach = ring as f32 * self.cell_size;
+            if matches!(best, Some((_, _, distance)) if distance <= reach * reach) {
+                break;
+            }
+        }
+        best.map(|(entity, position, _)| (entity, position))
+    }
+
+    /// The highest entity passing `filter` whose `size` rectangle, centered on its position,
+    /// contains `point`.
+    pub fn at(
+        &self,
+        point: Vec2,
+        size: Vec2,
+        mut filter: impl FnMut(Entity) -> bool,
+    ) -> Option<Entity> {
+        let half_size = size / 2.0;
+        self.within(point, half_size.length())
+            .filter(|(_, position)| {
+                let delta = (position.truncate() - point).abs();
+                delta.x <= half_size.x && delta.y <= half_size.y
+            })
+            .filter(|(entity, _)| filter(*entity))
+            .max_by(|(a, a_position), (b, b_position)| {
+                a_position
+                    .z
+                    .total_cmp(&b_position.z)
+                    .then_with(|| b.id().cmp(&a.id()))
+            })
+            .map(|(entity, _)| entity)
+    }
+
+    /// `desired` if nothing is closer to it than `spacing`, otherwise the closest free spot found
+    /// by walking outwards in steps of `spacing`. Gives up and returns `desired` after a few
+    /// rings.
+    pub fn free_position_near(&self, desired: Vec2, spacing: f32) -> Vec2 {
+        const MAX_RINGS: i32 = 8;
+        let is_free = |position: Vec2| self.within(position, spacing * 0.99).next().is_none();
+        for ring in 0..=MAX_RINGS {
+            let mut candidates: Vec<Vec2> = ring_cells(IVec2::ZERO, ring)
+                .map(|offset| desired + offset.as_vec2() * spacing)
+                .collect();
+            candidates.sort_by(|a, b| {
+                a.distance_squared(desired)
+                    .total_cmp(&b.distance_squared(desired))
+            });
+            if let Some(position) = candidates.into_iter().find(|position| is_free(*position)) {
+                return position;
+            }
+        }
+        desired
+    }
+}
+
+/// The cells `ring` steps away from `center`, counting diagonals, in a fixed order.
+fn ring_cells(center: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    (-ring..=ring)
+        .flat_map(move |x| (-ring..=ring).map(move |y| IVec2::new(x, y)))
+        .filter(move |offset| offset.x.abs() == ring || offset.y.abs() == ring)
+        .map(move |offset| center + offset)
+}
+
+/// Moves cards whose transform changed to their new cell and drops despawned cards.
+pub fn index_cards(
+    mut index: ResMut<SpatialIndex>,
+    cards: Query<(Entity, &Transform), (With<Card>, Changed<Transform>)>,
+    removed: RemovedComponents<Card>,
+) {
+    for entity in removed.iter() {
+        index.remove(entity);
+    }
+    for (entity, transform) in &cards {
+        index.insert(entity, transform.translation);
+    }
+}
@@ -0,0 +1,101 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use crate::game::{
+    card_definition::CardRegistry,
+    recipe::Recipes,
+    simulation::{SimulationClock, TimeScale},
+};
+
+/// Where the game is at. Board input and the simulation only run while [`GameState::Playing`];
+/// pausing pushes [`GameState::Paused`] on top of it so the board is left as it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    /// Waiting for the card definitions and recipes.
+    Loading,
+    MainMenu,
+    Playing,
+    Paused,
+    /// Every villager died. The board stays up until the next game starts.
+    GameOver,
+    /// Every objective of the scenario was met.
+    Won,
+    /// Building a scenario with the board editor. The simulation doesn't run, but cards can be
+    /// moved and stacked like in a game.
+    Editing,
+}
+
+/// The state entered once loading is done. The main menu points this at [`GameState::MainMenu`];
+/// without it, e.g. when running headless, a game starts right away.
+pub struct AfterLoading(pub GameState);
+
+impl Default for AfterLoading {
+    fn default() -> Self {
+        Self(GameState::Playing)
+    }
+}
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(GameState::Loading)
+            .init_resource::<AfterLoading>()
+            .add_system_set(SystemSet::on_update(GameState::Loading).with_system(finish_loading))
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_clock))
+            .add_system_set(SystemSet::on_resume(GameState::Playing).with_system(resume_clock))
+            .add_system_set(SystemSet::on_pause(GameState::Playing).with_system(stop_clock))
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(stop_clock));
+    }
+}
+
+/// Run criteria for board systems outside [`SimulationStage`](crate::game::simulation::SimulationStage),
+/// which only runs while playing anyway.
+pub fn playing(state: Res<State<GameState>>) -> ShouldRun {
+    if *state.current() == GameState::Playing {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Run criteria for picking up, dropping and stacking cards, which also works in the editor.
+pub fn board_input(state: Res<State<GameState>>) -> ShouldRun {
+    if is_board_input_state(*state.current()) {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+pub fn is_board_input_state(state: GameState) -> bool {
+    matches!(state, GameState::Playing | GameState::Editing)
+}
+
+fn finish_loading(
+    card_registry: Res<CardRegistry>,
+    recipes: Res<Recipes>,
+    after_loading: Res<AfterLoading>,
+    mut state: ResMut<State<GameState>>,
+) {
+    // cards take their stats from the definitions asset and stacks look up the recipes asset, so
+    // wait for both before dealing the board
+    if card_registry.is_loaded() && recipes.is_loaded() {
+        state.set(after_loading.0).unwrap();
+    }
+}
+
+/// Every game starts from tick 0, so replays line up no matter how long the menus took, and at
+/// normal speed, whatever speed the last game was left at.
+fn start_clock(mut clock: ResMut<SimulationClock>, mut time_scale: ResMut<TimeScale>) {
+    *clock = SimulationClock::default();
+    *time_scale = TimeScale::default();
+    clock.start();
+}
+
+fn resume_clock(mut clock: ResMut<SimulationClock>) {
+    clock.start();
+}
+
+fn stop_clock(mut clock: ResMut<SimulationClock>) {
+    clock.stop();
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::game::{
+    card::{Card, CardBundle, CardType},
+    spatial::SpatialIndex,
+    state::GameState,
+};
+
+/// Benchmark for big boards, added by `--stress <cards>`. Deals `cards` extra villagers, logs and
+/// goblins around the board when a game starts and logs how long frames take to update every few
+/// seconds. Best run with `--headless` in release mode, where rendering doesn't get in the way.
+///
+/// With rendering, the reports also count mesh and material assets. Those should stay flat no
+/// matter how long the game runs, as cards, tiles and progress bars share theirs.
+pub struct StressTestPlugin {
+    pub cards: usize,
+}
+
+impl Plugin for StressTestPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StressTest {
+            cards: self.cards,
+            frame_start: None,
+            frames: 0,
+            total: Duration::ZERO,
+            worst: Duration::ZERO,
+            report: Timer::from_seconds(StressTest::REPORT_INTERVAL, true),
+        })
+        .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(deal_stress_cards))
+        .add_system_to_stage(CoreStage::First, start_frame)
+        .add_system_to_stage(CoreStage::Last, end_frame);
+    }
+}
+
+pub struct StressTest {
+    cards: usize,
+    frame_start: Option<Instant>,
+    frames: u32,
+    total: Duration,
+    worst: Duration,
+    report: Timer,
+}
+
+impl StressTest {
+    /// Seconds between reports.
+    const REPORT_INTERVAL: f32 = 5.0;
+    /// Space between dealt cards.
+    const SPACING: f32 = 1.2;
+}
+
+fn deal_stress_cards(mut commands: Commands, stress_test: Res<StressTest>) {
+    // a square around the starting board, leaving the middle free
+    let side = (stress_test.cards as f32).sqrt().ceil() as i32 + 6;
+    let rng = fastrand::Rng::with_seed(0);
+    let locations = (-side / 2..side / 2)
+        .flat_map(|x| (-side / 2..side / 2).map(move |y| IVec2::new(x, y)))
+        .filter(|location| location.x.abs() > 3 || location.y.abs() > 3);
+    for location in locations.take(stress_test.cards) {
+        let card_type = match rng.u32(..10) {
+            0 => CardType::GOBLIN,
+            1..=4 => CardType::VILLAGER,
+            _ => CardType::LOG,
+        };
+        let position = location.as_vec2() * StressTest::SPACING;
+        commands.spawn_bundle(CardBundle {
+            transform: Transform::from_translation(position.extend(0.0)),
+            card: Card::from(card_type),
+            ..default()
+        });
+    }
+    info!("dealt {} stress test cards", stress_test.cards);
+}
+
+fn start_frame(mut stress_test: ResMut<StressTest>) {
+    stress_test.frame_start = Some(Instant::now());
+}
+
+fn end_frame(
+    time: Res<Time>,
+    spatial_index: Res<SpatialIndex>,
+    meshes: Option<Res<Assets<Mesh>>>,
+    materials: Option<Res<Assets<StandardMaterial>>>,
+    mut stress_test: ResMut<StressTest>,
+) {
+    let elapsed = match stress_test.frame_start.take() {
+        Some(start) => start.elapsed(),
+        None => return,
+    };
+    stress_test.frames += 1;
+    stress_test.total += elapsed;
+    stress_test.worst = stress_test.worst.max(elapsed);
+    if !stress_test.report.tick(time.delta()).just_finished() {
+        return;
+    }
+    info!(
+        "{} cards: {:.2}ms per frame on average, {:.2}ms at worst",
+        spatial_index.len(),
+        stress_test.total.as_secs_f64() * 1000.0 / stress_test.frames as f64,
+        stress_test.worst.as_secs_f64() * 1000.0,
+    );
+    if let (Some(meshes), Some(materials)) = (meshes, materials) {
+        info!("{} meshes, {} materials", meshes.len(), materials.len());
+    }
+    stress_test.frames = 0;
+    stress_test.total = Duration::ZERO;
+    stress_test.worst = Duration::ZERO;
+}
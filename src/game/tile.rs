@@ -1,63 +1,225 @@
 use std::time::Duration;
 
 use bevy::{prelude::*, utils::HashMap};
-use bevy_rapier3d::prelude::Collider;
+use serde::{Deserialize, Serialize};
 
 use crate::game::{
     card::{Card, CardBundle, CardClass, CardType, HoverPoint, SelectedCard},
-    progress_bar::{self, ProgressBar, ProgressBarBundle, ProgressBarStatus},
+    combat::{fight, Dying},
+    config::GameConfig,
+    events::{TileCleared, TileExhausted, TileProduced},
+    progress_bar::{ProgressBar, ProgressBarBundle, ProgressBarState},
+    simulation::{SimulationClock, SimulationStage},
+    spatial::{index_cards, SpatialIndex},
+    state::GameState,
 };
 
 pub struct TilePlugin;
 
 impl Plugin for TilePlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<TileData>()
-            .init_resource::<TileGrid>()
+        app.init_resource::<TileGrid>()
             .init_resource::<HoveredTile>()
-            .add_startup_system(spawn_tiles)
+            .init_resource::<EnemyTileSpawner>()
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_tiles))
+            .add_system_set(SystemSet::on_enter(GameState::Editing).with_system(spawn_tiles))
+            .add_system_to_stage(
+                SimulationStage,
+                init_tile
+                    .before(index_cards)
+                    .before(evaluate_tiles)
+                    .before(enemy_tile_spawner),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, init_tile)
+            .add_system_to_stage(SimulationStage, evaluate_tiles)
+            .add_system_to_stage(SimulationStage, regrow_tiles.after(evaluate_tiles))
+            .add_system_to_stage(SimulationStage, assault_enemy_tiles.after(fight))
+            .add_system_to_stage(
+                SimulationStage,
+                update_status_bars
+                    .after(regrow_tiles)
+                    .after(assault_enemy_tiles),
+            )
+            .add_system_to_stage(SimulationStage, enemy_tile_spawner)
+            .add_system(rebuild_enemy_tile_spawner);
+    }
+}
+
+pub struct TilePresentationPlugin;
+
+impl Plugin for TilePresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TileData>()
             .add_system_to_stage(CoreStage::PostUpdate, on_spawn_tile)
-            .add_system(hover_tile.after(crate::game::card::select_card))
-            .add_system(evaluate_tiles.after(hover_tile));
+            .add_system(hover_tile.after(crate::game::card::select_card));
     }
 }
 
-fn spawn_tiles(mut commands: Commands, tile_data: Res<TileData>) {
-    for x in -1..2 {
-        for y in -1..2 {
-            commands.spawn_bundle(TileBundle {
-                tile: Tile::Woods {
-                    slotted_villager: None,
-                    progress_bar: None,
-                },
-                tile_grid_location: TileGridLocation(IVec2::new(x, y)),
-                ..default()
-            });
-        }
+pub fn spawn_tiles(mut commands: Commands, config: Res<GameConfig>) {
+    for starting_tile in &config.tiles {
+        commands.spawn_bundle(TileBundle {
+            tile: starting_tile.kind.into(),
+            tile_grid_location: TileGridLocation(IVec2::from(starting_tile.location)),
+            ..default()
+        });
     }
-    commands.spawn_bundle(TileBundle {
-        tile: Tile::Enemies { progress_bar: None },
-        tile_grid_location: TileGridLocation(IVec2::new(0, 2)),
-        ..default()
-    });
 }
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Clone, Copy, PartialEq)]
 pub enum Tile {
-    Woods {
-        slotted_villager: Option<Entity>,
+    /// Makes cards while villagers are slotted in, see [`TileKind::production`]. Only the first
+    /// [`Production::slots`] entries of `slotted_villagers` are used.
+    Producer {
+        kind: TileKind,
+        slotted_villagers: [Option<Entity>; Tile::MAX_SLOTS],
         progress_bar: Option<Entity>,
+        /// Cards left to make. The tile is exhausted at 0 and can't be worked until it grows back.
+        stock: u32,
+        /// Seconds since the stock last grew.
+        regrowth: f32,
+        status_bar: Option<Entity>,
     },
+    /// Spawns goblins until a squad of villagers dropped on it wears down its
+    /// [`GameConfig::enemy_tile_health`], which turns it into woods.
     Enemies {
         progress_bar: Option<Entity>,
+        /// The bottom card of the stack assaulting the tile.
+        squad: Option<Entity>,
+        damage_taken: u32,
+        /// Damage dealt towards the next point of `damage_taken`.
+        assault: f32,
+        status_bar: Option<Entity>,
     },
 }
 
 impl Default for Tile {
     fn default() -> Self {
-        Self::Woods {
-            slotted_villager: None,
-            progress_bar: None,
+        TileKind::Woods.into()
+    }
+}
+
+/// A [`Tile`] without any state, for describing boards.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum TileKind {
+    Woods,
+    Quarry,
+    Farm,
+    BerryBush,
+    River,
+    Enemies,
+}
+
+impl From<TileKind> for Tile {
+    fn from(kind: TileKind) -> Self {
+        match kind {
+            TileKind::Enemies => Tile::Enemies {
+                progress_bar: None,
+                squad: None,
+                damage_taken: 0,
+                assault: 0.0,
+                status_bar: None,
+            },
+            kind => Tile::Producer {
+                kind,
+                slotted_villagers: [None; Tile::MAX_SLOTS],
+                progress_bar: None,
+                stock: kind.production().map_or(0, |production| production.stock),
+                regrowth: 0.0,
+                status_bar: None,
+            },
+        }
+    }
+}
+
+/// What a producing tile makes while villagers work it.
+pub struct Production {
+    /// Seconds per card for a single villager with a
+    /// [`work_speed`](crate::game::card::CardStats::work_speed) of 1, unless the scenario
+    /// overrides it in [`GameConfig::production_durations`]. Every slotted villager adds its work
+    /// speed to the rate the tile progresses at.
+    pub duration: f32,
+    /// How many villagers can work the tile at once, up to [`Tile::MAX_SLOTS`].
+    pub slots: usize,
+    /// How many cards a fresh tile holds.
+    pub stock: u32,
+    /// Seconds for one card of stock to grow back, whether the tile is worked or not.
+    pub regrowth: f32,
+    /// The cards that can come out, with their relative weights.
+    pub outputs: &'static [(CardType, u32)],
+}
+
+impl Production {
+    fn pick_output(&self, rng: &fastrand::Rng) -> &CardType {
+        let total: u32 = self.outputs.iter().map(|(_, weight)| weight).sum();
+        let mut roll = rng.u32(..total.max(1));
+        for (card_type, weight) in self.outputs {
+            if roll < *weight {
+                return card_type;
+            }
+            roll -= weight;
+        }
+        &self.outputs[0].0
+    }
+}
+
+impl TileKind {
+    pub const ALL: [TileKind; 6] = [
+        TileKind::Woods,
+        TileKind::Quarry,
+        TileKind::Farm,
+        TileKind::BerryBush,
+        TileKind::River,
+        TileKind::Enemies,
+    ];
+
+    pub fn production(self) -> Option<Production> {
+        let (duration, slots, stock, regrowth, outputs): (
+            f32,
+            usize,
+            u32,
+            f32,
+            &'static [(CardType, u32)],
+        ) = match self {
+            TileKind::Woods => (15.0, 1, 6, 60.0, &[(CardType::LOG, 1)]),
+            TileKind::Quarry => (20.0, 2, 8, 120.0, &[(CardType::STONE, 1)]),
+            TileKind::Farm => (25.0, 3, 4, 45.0, &[(CardType::WHEAT, 1)]),
+            TileKind::BerryBush => (10.0, 1, 5, 40.0, &[(CardType::BERRIES, 1)]),
+            TileKind::River => (
+                12.0,
+                2,
+                10,
+                30.0,
+                &[(CardType::FISH, 3), (CardType::STONE, 1)],
+            ),
+            TileKind::Enemies => return None,
+        };
+        Some(Production {
+            duration,
+            slots,
+            stock,
+            regrowth,
+            outputs,
+        })
+    }
+
+    fn texture(self) -> &'static str {
+        match self {
+            TileKind::Woods | TileKind::Enemies => "tile_woods.png",
+            TileKind::Quarry => "tile_quarry.png",
+            TileKind::Farm => "tile_farm.png",
+            TileKind::BerryBush => "tile_berry_bush.png",
+            TileKind::River => "tile_river.png",
+        }
+    }
+
+    fn tint(self) -> Color {
+        match self {
+            TileKind::Woods => Color::rgb_u8(90, 110, 90),
+            TileKind::Quarry => Color::rgb_u8(110, 105, 100),
+            TileKind::Farm => Color::rgb_u8(125, 115, 80),
+            TileKind::BerryBush => Color::rgb_u8(100, 85, 110),
+            TileKind::River => Color::rgb_u8(80, 100, 125),
+            TileKind::Enemies => Color::rgb_u8(60, 60, 60),
         }
     }
 }
@@ -68,6 +230,7 @@ impl Tile {
     pub const TILE_SLOT_ASPECT_RATIO: f32 = 50.0 / 60.0;
     pub const TILE_SLOT_SIZE: f32 = 1.2;
     pub const SPAWN_OFFSET: f32 = 0.95;
+    pub const MAX_SLOTS: usize = 3;
 
     pub fn grid_to_translation(grid_location: IVec2) -> Vec3 {
         (grid_location.as_vec2() * (Self::SIZE + Self::OFFSET)).extend(0.0)
@@ -84,53 +247,207 @@ impl Tile {
         Tile::TILE_SLOT_SIZE * Vec2::new(Tile::TILE_SLOT_ASPECT_RATIO, 1.0)
     }
 
-    pub fn has_slot(&self) -> bool {
+    /// How many villagers the tile takes.
+    pub fn slot_count(&self) -> usize {
         match self {
-            Tile::Woods {
-                slotted_villager,
+            Tile::Producer { kind, .. } => kind
+                .production()
+                .map_or(0, |production| production.slots.min(Tile::MAX_SLOTS)),
+            Tile::Enemies { .. } => 0,
+        }
+    }
+
+    /// Where slot `index` sits relative to the center of the tile. Slots are spread evenly across
+    /// the width of the tile.
+    pub fn slot_offset(&self, index: usize) -> Vec2 {
+        let count = self.slot_count().max(1) as f32;
+        let spacing = Self::SIZE.x / count;
+        Vec2::new((index as f32 + 0.5) * spacing - Self::SIZE.x / 2.0, 0.0)
+    }
+
+    /// The slot under `offset`, relative to the center of the tile.
+    pub fn slot_at(&self, offset: Vec2) -> Option<usize> {
+        let half_size = Self::slot_size() / 2.0;
+        (0..self.slot_count()).find(|i| {
+            let delta = (offset - self.slot_offset(*i)).abs();
+            delta.x < half_size.x && delta.y < half_size.y
+        })
+    }
+
+    /// The villagers working the tile, by slot.
+    pub fn slotted_villagers(&self) -> &[Option<Entity>] {
+        match self {
+            Tile::Producer {
+                slotted_villagers, ..
+            } => &slotted_villagers[..self.slot_count()],
+            Tile::Enemies { .. } => &[],
+        }
+    }
+
+    /// The slot `card` is in, if any.
+    pub fn slot_of(&self, card: Entity) -> Option<usize> {
+        self.slotted_villagers()
+            .iter()
+            .position(|villager| *villager == Some(card))
+    }
+
+    pub fn kind(&self) -> TileKind {
+        match self {
+            Tile::Producer { kind, .. } => *kind,
+            Tile::Enemies { .. } => TileKind::Enemies,
+        }
+    }
+
+    pub fn progress_bar(&self, current: f32, config: &GameConfig) -> ProgressBarBundle {
+        let (total, width) = match self {
+            Tile::Producer { kind, .. } => (config.production_duration(*kind), 0.85),
+            Tile::Enemies { .. } => (config.enemies_duration, 1.0),
+        };
+        ProgressBarBundle {
+            progress_bar: ProgressBar {
+                current,
+                total,
+                width,
+                height: 0.15,
+                padding: 0.05,
+                label: true,
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 1.0, 0.0),
+            ..default()
+        }
+    }
+
+    /// What the bar along the bottom edge of the tile shows: the stock left on producing tiles
+    /// and the health left on enemy tiles.
+    pub fn status(&self, config: &GameConfig) -> (f32, f32) {
+        match self {
+            Tile::Producer { kind, stock, .. } => (
+                *stock as f32,
+                kind.production().map_or(0, |production| production.stock) as f32,
+            ),
+            Tile::Enemies { damage_taken, .. } => (
+                config.enemy_tile_health.saturating_sub(*damage_taken) as f32,
+                config.enemy_tile_health as f32,
+            ),
+        }
+    }
+
+    pub fn status_bar(&self, config: &GameConfig) -> ProgressBarBundle {
+        let (current, total) = self.status(config);
+        ProgressBarBundle {
+            progress_bar: ProgressBar {
+                current,
+                total,
+                width: 0.85,
+                height: 0.1,
+                padding: 0.03,
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, -1.2, 0.0),
+            ..default()
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        matches!(self, Tile::Producer { stock: 0, .. })
+    }
+
+    /// Empties every slot and stops production, returning who was in which slot.
+    pub fn eject_villagers(
+        &mut self,
+        commands: &mut Commands,
+    ) -> [Option<Entity>; Tile::MAX_SLOTS] {
+        match self {
+            Tile::Producer {
+                slotted_villagers,
+                progress_bar,
+                ..
+            } => {
+                if let Some(progress_bar) = progress_bar.take() {
+                    commands.entity(progress_bar).despawn_recursive();
+                }
+                std::mem::take(slotted_villagers)
+            }
+            Tile::Enemies { .. } => [None; Tile::MAX_SLOTS],
+        }
+    }
+
+    /// Frees the slot `card` is in. Production stops, losing its progress, once the last
+    /// villager leaves.
+    pub fn unslot_card(&mut self, commands: &mut Commands, card: Entity) {
+        match self {
+            Tile::Producer {
+                slotted_villagers,
                 progress_bar,
-            } => true,
-            Tile::Enemies { progress_bar } => false,
+                ..
+            } => {
+                for villager in slotted_villagers.iter_mut() {
+                    if *villager == Some(card) {
+                        *villager = None;
+                    }
+                }
+                if slotted_villagers.iter().all(Option::is_none) {
+                    if let Some(progress_bar) = progress_bar.take() {
+                        commands.entity(progress_bar).despawn_recursive();
+                    }
+                }
+            }
+            Tile::Enemies { squad, .. } => {
+                if *squad == Some(card) {
+                    *squad = None;
+                }
+            }
         }
     }
 
+    /// Sends the stack rooted at `root` to assault the tile, if it's an enemy tile nobody is
+    /// assaulting yet. Only stacks of villagers can be sent, see
+    /// [`is_villager_squad`](crate::game::card::is_villager_squad).
+    pub fn try_assaulting(&mut self, root: Entity) -> bool {
+        match self {
+            Tile::Enemies { squad, .. } if squad.is_none() => {
+                *squad = Some(root);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Puts `card` in slot `index` if it's a villager, the slot is free and the tile isn't
+    /// exhausted. The first villager starts production.
     pub fn try_slotting_card(
         &mut self,
         commands: &mut Commands,
         tile_entity: Entity,
+        index: usize,
         card_entity: Entity,
         card: &Card,
+        config: &GameConfig,
     ) -> bool {
+        let bar = self.progress_bar(0.0, config);
+        let slot_count = self.slot_count();
         match self {
-            Tile::Woods {
-                slotted_villager,
+            Tile::Producer {
+                slotted_villagers,
                 progress_bar,
+                stock,
+                ..
             } => {
-                if slotted_villager.is_none() && card.class() == CardClass::Villager {
-                    *slotted_villager = Some(card_entity);
-                    let mut new_progress_bar = None;
+                if index >= slot_count
+                    || slotted_villagers[index].is_some()
+                    || *stock == 0
+                    || card.class() != CardClass::Villager
+                {
+                    return false;
+                }
+                slotted_villagers[index] = Some(card_entity);
+                if progress_bar.is_none() {
                     commands.entity(tile_entity).with_children(|parent| {
-                        new_progress_bar = Some(
-                            parent
-                                .spawn_bundle(ProgressBarBundle {
-                                    progress_bar: ProgressBar {
-                                        current: 0.0,
-                                        total: 15.0,
-                                        width: 0.85,
-                                        height: 0.15,
-                                        padding: 0.05,
-                                    },
-                                    transform: Transform::from_xyz(0.0, 1.0, 0.0),
-                                    ..default()
-                                })
-                                .id(),
-                        );
+                        *progress_bar = Some(parent.spawn_bundle(bar).id());
                     });
-                    *progress_bar = new_progress_bar;
-                    true
-                } else {
-                    false
                 }
+                true
             }
             _ => false,
         }
@@ -138,10 +455,11 @@ impl Tile {
 }
 
 #[derive(Component, Default, Clone, Copy, PartialEq, Eq, Deref, DerefMut)]
-pub struct TileGridLocation(IVec2);
+pub struct TileGridLocation(pub IVec2);
 
+/// The slot highlights of a tile, one per slot.
 #[derive(Component)]
-pub struct TileSlotEffect(Entity);
+pub struct TileSlotEffects(Vec<Entity>);
 
 #[derive(Bundle, Default)]
 pub struct TileBundle {
@@ -155,8 +473,7 @@ pub struct TileBundle {
 
 pub struct TileData {
     mesh: Handle<Mesh>,
-    woods_material: Handle<StandardMaterial>,
-    enemies_material: Handle<StandardMaterial>,
+    materials: HashMap<TileKind, Handle<StandardMaterial>>,
     tile_slot_mesh: Handle<Mesh>,
     tile_slot_material: Handle<StandardMaterial>,
 }
@@ -182,22 +499,20 @@ impl FromWorld for TileData {
                 }
                 .into(),
             ),
-            woods_material: materials.add(StandardMaterial {
-                base_color_texture: Some(asset_server.load("tile_woods.png")),
-                base_color: Color::rgb_u8(90, 110, 90),
-                unlit: true,
-                depth_bias: -10.0,
-                alpha_mode: AlphaMode::Blend,
-                ..default()
-            }),
-            enemies_material: materials.add(StandardMaterial {
-                base_color_texture: Some(asset_server.load("tile_woods.png")),
-                base_color: Color::rgb_u8(60, 60, 60),
-                unlit: true,
-                depth_bias: -10.0,
-                alpha_mode: AlphaMode::Blend,
-                ..default()
-            }),
+            materials: TileKind::ALL
+                .into_iter()
+                .map(|kind| {
+                    let material = materials.add(StandardMaterial {
+                        base_color_texture: Some(asset_server.load(kind.texture())),
+                        base_color: kind.tint(),
+                        unlit: true,
+                        depth_bias: -10.0,
+                        alpha_mode: AlphaMode::Blend,
+                        ..default()
+                    });
+                    (kind, material)
+                })
+                .collect(),
             tile_slot_material: materials.add(StandardMaterial {
                 base_color_texture: Some(asset_server.load("tile_slot.png")),
                 base_color: Color::rgba_u8(255, 255, 255, 100),
@@ -213,89 +528,208 @@ impl FromWorld for TileData {
 #[derive(Default, Deref, DerefMut)]
 pub struct TileGrid(HashMap<IVec2, Entity>);
 
-fn on_spawn_tile(
+/// Puts new tiles on the grid and gives them their bars. Like `init_cards`, this runs at the start
+/// of every simulation step as well as after `Update`, and leaves tiles that already have their
+/// bars alone.
+fn init_tile(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    tile_data: Res<TileData>,
     mut tile_grid: ResMut<TileGrid>,
+    config: Res<GameConfig>,
     mut tiles: Query<(Entity, &mut Tile, &TileGridLocation, &mut Transform), Added<Tile>>,
 ) {
     for (entity, mut tile, location, mut transform) in &mut tiles {
         tile_grid.insert(location.0, entity);
         transform.translation = Tile::grid_to_translation(location.0);
-        let mut tile_slot = None;
+        let bar = tile.progress_bar(0.0, &config);
+        let new_status_bar = tile.status_bar(&config);
         match &mut *tile {
-            Tile::Woods {
-                slotted_villager,
-                progress_bar,
-            } => {
-                commands.entity(entity).with_children(|parent| {
-                    parent.spawn_bundle(PbrBundle {
-                        material: tile_data.woods_material.clone(),
-                        mesh: tile_data.mesh.clone(),
-                        ..default()
+            Tile::Producer { status_bar, .. } | Tile::Enemies { status_bar, .. } => {
+                if status_bar.is_none() {
+                    commands.entity(entity).with_children(|parent| {
+                        *status_bar = Some(parent.spawn_bundle(new_status_bar).id());
                     });
-                });
+                }
             }
-            Tile::Enemies { progress_bar } => {
-                commands.entity(entity).with_children(|parent| {
-                    parent.spawn_bundle(PbrBundle {
-                        material: tile_data.enemies_material.clone(),
-                        mesh: tile_data.mesh.clone(),
-                        ..default()
+        }
+        match &mut *tile {
+            Tile::Producer { .. } => {}
+            Tile::Enemies { progress_bar, .. } => {
+                // restored tiles arrive with their progress bar already spawned
+                if progress_bar.is_none() {
+                    commands.entity(entity).with_children(|parent| {
+                        *progress_bar = Some(parent.spawn_bundle(bar).id());
                     });
-
-                    *progress_bar = Some(
-                        parent
-                            .spawn_bundle(ProgressBarBundle {
-                                progress_bar: ProgressBar {
-                                    current: 0.0,
-                                    total: 20.0,
-                                    width: 1.0,
-                                    height: 0.15,
-                                    padding: 0.05,
-                                },
-                                transform: Transform::from_xyz(0.0, 1.0, 0.0),
-                                ..default()
-                            })
-                            .id(),
-                    );
-                });
+                }
             }
         }
+    }
+}
 
-        if tile.has_slot() {
-            commands.entity(entity).with_children(|parent| {
-                tile_slot = Some(
+fn on_spawn_tile(
+    mut commands: Commands,
+    tile_data: Res<TileData>,
+    tiles: Query<(Entity, &Tile), Added<Tile>>,
+) {
+    for (entity, tile) in &tiles {
+        let mut tile_slots = Vec::new();
+        let material = tile_data.materials[&tile.kind()].clone();
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn_bundle(PbrBundle {
+                material,
+                mesh: tile_data.mesh.clone(),
+                ..default()
+            });
+        });
+
+        commands.entity(entity).with_children(|parent| {
+            for i in 0..tile.slot_count() {
+                tile_slots.push(
                     parent
                         .spawn_bundle(PbrBundle {
                             material: tile_data.tile_slot_material.clone(),
                             mesh: tile_data.tile_slot_mesh.clone(),
-                            transform: Transform::from_xyz(0.0, 0.0, 0.001),
+                            transform: Transform::from_translation(
+                                tile.slot_offset(i).extend(0.001),
+                            ),
                             visibility: Visibility { is_visible: false },
                             ..default()
                         })
                         .id(),
                 );
-            });
+            }
+        });
+        if !tile_slots.is_empty() {
+            commands.entity(entity).insert(TileSlotEffects(tile_slots));
         }
-        if let Some(tile_slot) = tile_slot {
-            commands.entity(entity).insert(TileSlotEffect(tile_slot));
+    }
+}
+
+/// Grows the map: every `interval` seconds a new enemy tile appears on the frontier of the
+/// [`TileGrid`].
+pub struct EnemyTileSpawner {
+    timer: Timer,
+    min_start_distance: i32,
+    seed: u64,
+    rng_state: u64,
+}
+
+impl FromWorld for EnemyTileSpawner {
+    fn from_world(world: &mut World) -> Self {
+        let config = &world.resource::<GameConfig>().enemy_tiles;
+        Self::new(config.interval, config.min_start_distance, config.seed)
+    }
+}
+
+impl EnemyTileSpawner {
+    /// New tiles are placed at least `min_start_distance` tiles (counting diagonals) away from the
+    /// center of the grid. The starting woods are within one tile of it, so anything below 3 could
+    /// place enemies right next to them.
+    pub fn new(interval: f32, min_start_distance: i32, seed: u64) -> Self {
+        Self {
+            timer: Timer::from_seconds(interval, true),
+            min_start_distance,
+            seed,
+            rng_state: seed,
         }
     }
+
+    /// Starts over as if the spawner was new, for a new game.
+    pub fn reset(&mut self) {
+        self.timer.reset();
+        self.rng_state = self.seed;
+    }
+
+    pub fn state(&self) -> EnemyTileSpawnerState {
+        EnemyTileSpawnerState {
+            elapsed: self.timer.elapsed_secs(),
+            rng_state: self.rng_state,
+        }
+    }
+
+    /// Picks up where a saved game left off. The interval and distances still come from the
+    /// current config.
+    pub fn restore(&mut self, state: EnemyTileSpawnerState) {
+        self.reset();
+        self.timer
+            .set_elapsed(Duration::from_secs_f32(state.elapsed.max(0.0)));
+        self.rng_state = state.rng_state;
+    }
+
+    /// Picks a free location bordering the existing tiles, or the nearest free ring far enough out
+    /// if the whole frontier is still too close to the start.
+    fn choose_location(&mut self, tile_grid: &TileGrid) -> Option<IVec2> {
+        let min_start_distance = self.min_start_distance;
+        let start_distance = |location: &IVec2| location.x.abs().max(location.y.abs());
+        let is_free = |location: &IVec2| !tile_grid.contains_key(location);
+
+        let mut candidates: Vec<IVec2> = tile_grid
+            .keys()
+            .flat_map(|location| {
+                [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].map(|offset| *location + offset)
+            })
+            .filter(|location| is_free(location) && start_distance(location) >= min_start_distance)
+            .collect();
+        if candidates.is_empty() {
+            let r = min_start_distance;
+            candidates = (-r..=r)
+                .flat_map(|x| (-r..=r).map(move |y| IVec2::new(x, y)))
+                .filter(|location| is_free(location) && start_distance(location) == r)
+                .collect();
+        }
+        // grid iteration order isn't stable, sort so the same seed always picks the same tile
+        candidates.sort_by_key(|location| (location.x, location.y));
+        candidates.dedup();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let rng = fastrand::Rng::with_seed(self.rng_state);
+        let location = candidates[rng.usize(..candidates.len())];
+        self.rng_state = rng.u64(..);
+        Some(location)
+    }
 }
 
-pub fn enemy_tile_spawner(
+/// How far [`EnemyTileSpawner`] got towards the next tile and where its RNG is at, for saves.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct EnemyTileSpawnerState {
+    /// Seconds since the last tile.
+    pub elapsed: f32,
+    pub rng_state: u64,
+}
+
+fn enemy_tile_spawner(
     mut commands: Commands,
-    mut timer: Local<Option<Timer>>,
-    mut grid_size: Local<UVec2>,
-    time: Res<Time>,
+    clock: Res<SimulationClock>,
+    mut tile_grid: ResMut<TileGrid>,
+    mut spawner: ResMut<EnemyTileSpawner>,
 ) {
-    if *grid_size == UVec2::new(0, 0) {
-        *grid_size = UVec2::new(3, 3);
+    if !spawner.timer.tick(clock.delta()).just_finished() {
+        return;
+    }
+    match spawner.choose_location(&tile_grid) {
+        Some(location) => {
+            let tile = commands
+                .spawn_bundle(TileBundle {
+                    tile: TileKind::Enemies.into(),
+                    tile_grid_location: TileGridLocation(location),
+                    ..default()
+                })
+                .id();
+            // claim the location right away, init_tile only gets to it next step
+            tile_grid.insert(location, tile);
+        }
+        None => warn!("no free location for a new enemy tile"),
+    }
+}
+
+/// Picks up changes to [`GameConfig::enemy_tiles`] made by the editor. The spawner starts over,
+/// as it does for every new game.
+fn rebuild_enemy_tile_spawner(config: Res<GameConfig>, mut spawner: ResMut<EnemyTileSpawner>) {
+    if config.is_changed() && !config.is_added() {
+        let config = &config.enemy_tiles;
+        *spawner = EnemyTileSpawner::new(config.interval, config.min_start_distance, config.seed);
     }
-    let timer = timer.get_or_insert(Timer::new(Duration::from_secs_f32(60.0), true));
-    if timer.tick(time.delta()).just_finished() {}
 }
 
 #[derive(Default)]
@@ -304,96 +738,264 @@ pub struct HoveredTile(pub Option<Entity>);
 pub fn hover_tile(
     hover_point: Res<HoverPoint>,
     tile_grid: Res<TileGrid>,
-    mouse_input: Res<Input<MouseButton>>,
     mut hovered_tile: ResMut<HoveredTile>,
     selected_card: Res<SelectedCard>,
     mut visibilities: Query<&mut Visibility>,
-    tile_slots: Query<&TileSlotEffect>,
-    tiles: Query<(&Tile, &TileSlotEffect)>,
+    tiles: Query<(Entity, &Tile, &TileSlotEffects)>,
 ) {
-    if let Some(tile_entity) = hovered_tile.0 {
-        if let Ok(tile_slot) = tile_slots.get(tile_entity) {
-            let mut visibility = visibilities.get_mut(tile_slot.0).unwrap();
-            visibility.is_visible = false;
+    hovered_tile.0 = match (*selected_card, &*hover_point) {
+        (SelectedCard::Some(_), HoverPoint::Some(point)) => {
+            tile_grid.get(&Tile::translation_to_grid(*point)).copied()
+        }
+        (SelectedCard::Some(_), HoverPoint::None) => None,
+        // keep the last hovered tile around for the drop that just happened
+        (SelectedCard::None, _) => hovered_tile.0,
+    };
+
+    // occupied slots stay highlighted, and every slot of the tile a card is held over lights up
+    for (entity, tile, tile_slots) in tiles.iter() {
+        let hovered =
+            matches!(*selected_card, SelectedCard::Some(_)) && hovered_tile.0 == Some(entity);
+        for (villager, tile_slot) in tile.slotted_villagers().iter().zip(&tile_slots.0) {
+            let is_visible = hovered || villager.is_some();
+            let mut visibility = visibilities.get_mut(*tile_slot).unwrap();
+            if visibility.is_visible != is_visible {
+                visibility.is_visible = is_visible;
+            }
         }
     }
-    for (tile, tile_slot) in tiles.iter() {
-        match tile {
-            Tile::Woods {
-                slotted_villager, ..
-            } => {
-                let mut visibility = visibilities.get_mut(tile_slot.0).unwrap();
-                visibility.is_visible = slotted_villager.is_some();
+}
+
+pub fn evaluate_tiles(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut tiles: Query<(Entity, &mut Tile, &TileGridLocation, &Transform), Without<Card>>,
+    mut cards: Query<(&mut Card, &mut Transform)>,
+    mut progress_bars: Query<&mut ProgressBar>,
+    mut produced_events: EventWriter<TileProduced>,
+    mut exhausted_events: EventWriter<TileExhausted>,
+) {
+    for (entity, mut tile, location, transform) in &mut tiles {
+        let (progress_bar, spawn_offset, rate) = match *tile {
+            Tile::Producer { progress_bar, .. } => {
+                let rate: f32 = tile
+                    .slotted_villagers()
+                    .iter()
+                    .flatten()
+                    .filter_map(|villager| cards.get(*villager).ok())
+                    // villagers pulled into a fight leave their work be
+                    .filter(|(card, _)| card.combat_state.is_none())
+                    .map(|(card, _)| card.info.stats.work_speed)
+                    .sum();
+                (progress_bar, Tile::SPAWN_OFFSET, rate)
+            }
+            Tile::Enemies { progress_bar, .. } => (progress_bar, 0.0, 1.0),
+        };
+        let mut bar = match progress_bar.and_then(|bar| progress_bars.get_mut(bar).ok()) {
+            Some(bar) => bar,
+            None => continue,
+        };
+        let state = if rate > 0.0 {
+            ProgressBarState::Working
+        } else {
+            ProgressBarState::Blocked
+        };
+        // idle tiles leave their bar untouched, so it isn't redrawn every step
+        if bar.rate != rate || bar.state != state {
+            bar.rate = rate;
+            bar.state = state;
+        }
+        if bar.is_advancing() {
+            bar.tick(clock.delta_seconds());
+        }
+        if !bar.finished() {
+            continue;
+        }
+        bar.reset();
+
+        let card_type = match &mut *tile {
+            Tile::Producer { kind, stock, .. } => match kind.production() {
+                Some(production) => {
+                    *stock = stock.saturating_sub(1);
+                    // seeded from the tick and location, so the same game always rolls the same
+                    let seed = (location.x as u64) << 32 | location.y as u32 as u64;
+                    let rng = fastrand::Rng::with_seed(clock.tick() ^ seed);
+                    production.pick_output(&rng).clone()
+                }
+                None => continue,
+            },
+            Tile::Enemies { .. } => CardType::GOBLIN,
+        };
+        let position = spatial_index.free_position_near(
+            transform.translation.truncate() + Vec2::X * spawn_offset,
+            Card::SPAWN_OFFSET,
+        );
+        let card = commands
+            .spawn_bundle(CardBundle {
+                card: Card::from(card_type.clone()),
+                transform: Transform::from_translation(position.extend(0.0)),
+                ..default()
+            })
+            .id();
+        spatial_index.insert(card, position.extend(0.0));
+        produced_events.send(TileProduced {
+            tile: entity,
+            card,
+            card_type,
+        });
+
+        if tile.is_exhausted() {
+            // put the workers just below their slots, so they're easy to pick up again
+            let villagers = tile.eject_villagers(&mut commands);
+            for (i, villager) in villagers.iter().enumerate() {
+                if let Some(Ok((mut card, mut card_transform))) = villager.map(|v| cards.get_mut(v))
+                {
+                    card.slotted_in_tile = None;
+                    let offset = tile.slot_offset(i) - Vec2::Y * Tile::slot_size().y;
+                    card_transform.translation.x = transform.translation.x + offset.x;
+                    card_transform.translation.y = transform.translation.y + offset.y;
+                }
             }
-            _ => {}
+            exhausted_events.send(TileExhausted { tile: entity });
         }
     }
+}
 
-    if let SelectedCard::Some(_) = *selected_card {
-        if let HoverPoint::Some(point) = *hover_point {
-            let location = Tile::translation_to_grid(point);
-            if let Some(tile_entity) = tile_grid.get(&location) {
-                hovered_tile.0 = Some(*tile_entity);
-                let tile_slot = tile_slots.get(*tile_entity).unwrap().0;
-                let mut visibility = visibilities.get_mut(tile_slot).unwrap();
-                visibility.is_visible = true;
+/// Grows the stock of producing tiles back, one card at a time.
+fn regrow_tiles(clock: Res<SimulationClock>, mut tiles: Query<&mut Tile>) {
+    for mut tile in &mut tiles {
+        // leave full tiles unchanged, so their status bar isn't redrawn
+        let growing = match *tile {
+            Tile::Producer {
+                kind,
+                stock,
+                regrowth,
+                ..
+            } => kind
+                .production()
+                .is_some_and(|production| stock < production.stock || regrowth != 0.0),
+            Tile::Enemies { .. } => false,
+        };
+        if !growing {
+            continue;
+        }
+        if let Tile::Producer {
+            kind,
+            stock,
+            regrowth,
+            ..
+        } = &mut *tile
+        {
+            let production = match kind.production() {
+                Some(production) => production,
+                None => continue,
+            };
+            if *stock < production.stock {
+                *regrowth += clock.delta_seconds();
+                if *regrowth >= production.regrowth {
+                    *regrowth = 0.0;
+                    *stock += 1;
+                }
             } else {
-                hovered_tile.0 = None;
+                *regrowth = 0.0;
             }
-        } else {
-            hovered_tile.0 = None;
         }
     }
 }
 
-fn evaluate_tiles(
+/// Squads wear enemy tiles down by the damage per second of their villagers, leaving out those
+/// busy fighting goblins. A cleared tile turns into woods and the squad is let go.
+fn assault_enemy_tiles(
     mut commands: Commands,
-    time: Res<Time>,
-    mut tiles: Query<(&mut Tile, &Transform)>,
-    mut progress_bars: Query<&mut ProgressBar>,
+    clock: Res<SimulationClock>,
+    config: Res<GameConfig>,
+    mut tiles: Query<(Entity, &mut Tile, &TileGridLocation, &Transform), Without<Card>>,
+    mut cards: Query<(&mut Card, &mut Transform), Without<Dying>>,
+    mut cleared_events: EventWriter<TileCleared>,
 ) {
-    for (mut tile, transform) in &mut tiles {
-        match &mut *tile {
-            Tile::Woods {
-                slotted_villager,
-                progress_bar,
-            } => {
-                if let Some(bar_entity) = *progress_bar {
-                    if let Ok(mut bar) = progress_bars.get_mut(bar_entity) {
-                        bar.add(time.delta_seconds());
-                        if bar.finished() {
-                            commands.spawn_bundle(CardBundle {
-                                card: Card::from(CardType::Log),
-                                transform: Transform::from_xyz(
-                                    transform.translation.x + Tile::SPAWN_OFFSET,
-                                    transform.translation.y,
-                                    0.0,
-                                ),
-                                ..default()
-                            });
-                            bar.reset();
-                        }
-                    }
-                }
+    for (entity, mut tile, location, transform) in &mut tiles {
+        if !matches!(*tile, Tile::Enemies { squad: Some(_), .. }) {
+            continue;
+        }
+        let (squad, damage_taken, assault) = match &mut *tile {
+            Tile::Enemies {
+                squad,
+                damage_taken,
+                assault,
+                ..
+            } => (squad, damage_taken, assault),
+            _ => continue,
+        };
+        let root = match *squad {
+            Some(root) => root,
+            None => continue,
+        };
+        // the squad may have been picked up or killed since
+        if cards
+            .get(root)
+            .ok()
+            .is_none_or(|(card, _)| card.slotted_in_tile != Some(entity))
+        {
+            *squad = None;
+            *assault = 0.0;
+            continue;
+        }
+
+        let mut damage_per_second = 0.0;
+        let mut current = Some(root);
+        while let Some((card, _)) = current.and_then(|card| cards.get(card).ok()) {
+            if card.class() == CardClass::Villager && card.combat_state.is_none() {
+                let stats = &card.info.stats;
+                damage_per_second += stats.damage as f32 * stats.attack_speed;
             }
-            Tile::Enemies { progress_bar } => {
-                if let Some(bar_entity) = *progress_bar {
-                    if let Ok(mut bar) = progress_bars.get_mut(bar_entity) {
-                        bar.add(time.delta_seconds());
-                        if bar.finished() {
-                            commands.spawn_bundle(CardBundle {
-                                card: Card::from(CardType::Goblin),
-                                transform: Transform::from_xyz(
-                                    transform.translation.x,
-                                    transform.translation.y,
-                                    0.0,
-                                ),
-                                ..default()
-                            });
-                            bar.reset();
-                        }
-                    }
-                }
+            current = card.stack_child;
+        }
+        *assault += damage_per_second * clock.delta_seconds();
+        let damage = *assault as u32;
+        *assault -= damage as f32;
+        *damage_taken += damage;
+        if *damage_taken < config.enemy_tile_health {
+            continue;
+        }
+
+        // leave the squad standing where the tile was
+        if let Ok((mut card, mut card_transform)) = cards.get_mut(root) {
+            card.slotted_in_tile = None;
+            card_transform.translation.x = transform.translation.x;
+            card_transform.translation.y = transform.translation.y;
+        }
+        commands.entity(entity).despawn_recursive();
+        let woods = commands
+            .spawn_bundle(TileBundle {
+                tile: TileKind::Woods.into(),
+                tile_grid_location: *location,
+                ..default()
+            })
+            .id();
+        info!("enemy tile at {} cleared", location.0);
+        cleared_events.send(TileCleared {
+            tile: entity,
+            woods,
+            squad: root,
+        });
+    }
+}
+
+fn update_status_bars(
+    config: Res<GameConfig>,
+    tiles: Query<&Tile, Changed<Tile>>,
+    mut progress_bars: Query<&mut ProgressBar>,
+) {
+    for tile in &tiles {
+        let status_bar = match tile {
+            Tile::Producer { status_bar, .. } | Tile::Enemies { status_bar, .. } => *status_bar,
+        };
+        if let Some(mut bar) = status_bar.and_then(|bar| progress_bars.get_mut(bar).ok()) {
+            let (current, total) = tile.status(&config);
+            if bar.current != current || bar.total != total {
+                bar.current = current;
+                bar.total = total;
             }
         }
     }
@@ -1,26 +1,94 @@
-#[allow(dead_code, unused_variables, unused_mut, unused_imports)]
-mod game;
+use std::{path::Path, time::Duration};
 
-use bevy::{asset::AssetServerSettings, prelude::*};
-use bevy_rapier3d::prelude::*;
+use bevy::{app::ScheduleRunnerSettings, asset::AssetServerSettings, log::LogPlugin, prelude::*};
 
-use crate::game::GamePlugin;
+use card_combinator::game::{
+    config::GameConfig,
+    editor::EditorSettings,
+    replay::{Recorder, Replay},
+    state::{AfterLoading, GameState},
+    stress::StressTestPlugin,
+    GamePlugin, HeadlessPlugin,
+};
 
 fn main() {
-    App::new()
-        .insert_resource(AmbientLight {
+    let mut app = App::new();
+    app.insert_resource(AssetServerSettings {
+        watch_for_changes: true,
+        ..default()
+    });
+
+    let edit_path = arg_value("--edit");
+    let config = match arg_value("--scenario").or_else(|| edit_path.clone()) {
+        // new scenarios start from the default board
+        Some(path) if edit_path.is_some() && !Path::new(&path).exists() => GameConfig::default(),
+        Some(path) => match GameConfig::read(path.as_ref()) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("failed to read scenario {}: {}", path, err);
+                std::process::exit(1);
+            }
+        },
+        None => GameConfig::default(),
+    };
+
+    if let Some(path) = arg_value("--record") {
+        app.insert_resource(Recorder::new(path, config.clone()));
+    }
+    if let Some(path) = arg_value("--replay") {
+        match Replay::read(path.as_ref(), &config) {
+            Ok(replay) => {
+                app.insert_resource(replay);
+            }
+            Err(err) => {
+                eprintln!("failed to read replay {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if std::env::args().any(|arg| arg == "--headless") {
+        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )))
+        // not part of HeadlessPlugin, tests run many apps in one process but there's only one
+        // global logger
+        .add_plugin(LogPlugin)
+        .add_plugin(HeadlessPlugin { config });
+    } else {
+        app.insert_resource(AmbientLight {
             color: Color::WHITE,
             brightness: 0.4,
         })
-        .insert_resource(AssetServerSettings {
-            watch_for_changes: true,
-            ..default()
-        })
         .insert_resource(ClearColor(Color::rgb(0.2, 0.2, 0.2)))
         .add_plugins(DefaultPlugins)
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
         // .add_plugin(bevy_inspector_egui::WorldInspectorPlugin::new())
-        // .add_plugin(RapierDebugRenderPlugin::default())
-        .add_plugin(GamePlugin)
-        .run();
+        .add_plugin(GamePlugin { config });
+
+        if let Some(path) = edit_path {
+            app.insert_resource(EditorSettings { path: path.into() })
+                .insert_resource(AfterLoading(GameState::Editing));
+        }
+    }
+
+    if let Some(cards) = arg_value("--stress") {
+        match cards.parse() {
+            Ok(cards) => {
+                app.add_plugin(StressTestPlugin { cards });
+            }
+            Err(err) => {
+                eprintln!("invalid card count {} for --stress: {}", cards, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    app.run();
+}
+
+/// The argument following `name`, e.g. `--record session.replay.ron`.
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next();
+    args.next()
 }
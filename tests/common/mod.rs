@@ -0,0 +1,71 @@
+//! Helpers shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use card_combinator::game::{
+    card::{Card, CardType},
+    config::{EnemyTileConfig, GameConfig},
+    simulation::TimeScale,
+    state::GameState,
+    HeadlessPlugin,
+};
+
+/// An empty board that never spawns enemy tiles, for tests to deal their own cards on.
+pub fn empty_config() -> GameConfig {
+    GameConfig {
+        cards: Vec::new(),
+        stacks: Vec::new(),
+        tiles: Vec::new(),
+        enemy_tiles: EnemyTileConfig {
+            interval: 1_000_000.0,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+/// An app that has loaded the card definitions and dealt the board of `config`, running at 20x
+/// speed.
+pub fn start_game(config: GameConfig) -> App {
+    let mut app = App::new();
+    app.add_plugin(HeadlessPlugin { config });
+    start(&mut app);
+    app
+}
+
+/// Runs `app` until the card definitions are loaded and the board is dealt, then speeds it up to
+/// 20x.
+pub fn start(app: &mut App) {
+    run_until(app, Duration::from_secs(30), |world| {
+        *world.resource::<State<GameState>>().current() == GameState::Playing
+    });
+    // the speed is reset when the game starts
+    app.insert_resource(TimeScale(20.0));
+}
+
+/// Updates the app until `done` returns true, failing the test after `timeout` of real time.
+/// The simulation steps on real time, so each update waits for a bit of it to pass.
+pub fn run_until(app: &mut App, timeout: Duration, mut done: impl FnMut(&mut World) -> bool) {
+    let start = Instant::now();
+    loop {
+        app.update();
+        if done(&mut app.world) {
+            return;
+        }
+        assert!(start.elapsed() < timeout, "timed out after {:?}", timeout);
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+pub fn count_cards(world: &mut World, card_type: &CardType) -> usize {
+    world
+        .query::<&Card>()
+        .iter(world)
+        .filter(|card| card.card_type() == card_type)
+        .count()
+}
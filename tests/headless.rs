@@ -0,0 +1,278 @@
+mod common;
+
+use std::{path::Path, time::Duration};
+
+use bevy::{prelude::*, utils::HashMap};
+use card_combinator::game::{
+    card::{Card, CardBundle, CardType},
+    card_definition::CardRegistry,
+    combat::Engagement,
+    config::{GameConfig, StartingCard, StartingStack},
+    recipe::Recipes,
+    replay::{Replay, ReplayFile, REPLAY_VERSION},
+    save::{LoadRequest, SaveGame, SaveRequest},
+    simulation::{SimulationClock, TimeScale},
+    tile::{Tile, TileGrid, TileKind},
+};
+
+use common::{count_cards, empty_config, run_until, start_game};
+
+#[test]
+fn stacked_villagers_breed() {
+    let mut app = start_game(GameConfig {
+        stacks: vec![StartingStack {
+            cards: vec![CardType::VILLAGER, CardType::VILLAGER],
+            position: [0.0, 0.0],
+        }],
+        recipe_durations: [("breed".to_string(), 1.0)].into(),
+        ..empty_config()
+    });
+
+    assert_eq!(count_cards(&mut app.world, &CardType::VILLAGER), 2);
+
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        count_cards(world, &CardType::VILLAGER) == 3
+    });
+    let villagers: Vec<_> = app
+        .world
+        .query::<&Card>()
+        .iter(&app.world)
+        .filter(|card| card.card_type() == &CardType::VILLAGER)
+        .map(|card| card.info.stats.health)
+        .collect();
+    assert!(
+        villagers.iter().all(|health| *health > 0),
+        "new villagers get their stats from the definitions: {:?}",
+        villagers
+    );
+}
+
+fn engagements(world: &mut World) -> Vec<(usize, usize)> {
+    world
+        .query::<&Engagement>()
+        .iter(world)
+        .map(|engagement| (engagement.enemies.len(), engagement.villagers.len()))
+        .collect()
+}
+
+#[test]
+fn villagers_fight_goblins() {
+    let mut app = start_game(GameConfig {
+        cards: vec![
+            StartingCard {
+                card_type: CardType::VILLAGER,
+                position: [0.0, 0.0],
+            },
+            StartingCard {
+                card_type: CardType::GOBLIN,
+                position: [0.0, 3.0],
+            },
+        ],
+        ..empty_config()
+    });
+
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        !engagements(world).is_empty()
+    });
+    assert_eq!(engagements(&mut app.world), vec![(1, 1)]);
+
+    // a villager dealt next to the fight joins it
+    app.insert_resource(TimeScale(1.0));
+    let goblin = app
+        .world
+        .query::<(&Card, &Transform)>()
+        .iter(&app.world)
+        .find(|(card, _)| card.card_type() == &CardType::GOBLIN)
+        .map(|(_, transform)| transform.translation)
+        .unwrap();
+    app.world.spawn().insert_bundle(CardBundle {
+        card: Card::from(CardType::VILLAGER),
+        transform: Transform::from_translation(goblin + Vec3::new(0.5, 0.0, 0.0)),
+        ..default()
+    });
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        engagements(world) == vec![(1, 2)]
+    });
+
+    // villagers attack faster than goblins and the goblin goes down in one hit
+    app.insert_resource(TimeScale(20.0));
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        engagements(world).is_empty() && count_cards(world, &CardType::GOBLIN) == 0
+    });
+    let villagers: Vec<_> = app
+        .world
+        .query::<&Card>()
+        .iter(&app.world)
+        .filter(|card| card.card_type() == &CardType::VILLAGER)
+        .map(|card| (card.info.stats.health, card.combat_state.is_some()))
+        .collect();
+    assert_eq!(villagers, vec![(3, false), (3, false)]);
+}
+
+#[test]
+fn scenario_deals_its_board() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/last_stand.ron");
+    let config = GameConfig::read(&path).unwrap();
+    let mut app = start_game(config);
+    app.update();
+
+    assert_eq!(count_cards(&mut app.world, &CardType::VILLAGER), 1);
+    let mut tiles: Vec<(IVec2, TileKind)> = app
+        .world
+        .resource::<TileGrid>()
+        .iter()
+        .map(|(location, entity)| (*location, app.world.get::<Tile>(*entity).unwrap().kind()))
+        .collect();
+    tiles.sort_by_key(|(location, _)| (location.x, location.y));
+    assert_eq!(
+        tiles,
+        vec![
+            (IVec2::new(0, 0), TileKind::Woods),
+            (IVec2::new(0, 1), TileKind::Enemies),
+        ]
+    );
+}
+
+#[test]
+fn recipes_follow_config_changes() {
+    let mut app = start_game(GameConfig {
+        recipe_durations: [("breed".to_string(), 1.0)].into(),
+        ..empty_config()
+    });
+    let breed_duration = |app: &App| {
+        let recipes = app.world.resource::<Recipes>();
+        let mut card_types = HashMap::default();
+        card_types.insert(CardType::VILLAGER, 2);
+        recipes[recipes.find(&card_types).unwrap()].duration
+    };
+    assert_eq!(breed_duration(&app), 1.0);
+
+    app.world
+        .resource_mut::<GameConfig>()
+        .recipe_durations
+        .insert("breed".to_string(), 2.0);
+    app.update();
+    assert_eq!(breed_duration(&app), 2.0);
+}
+
+#[test]
+fn replays_only_play_on_their_scenario() {
+    let path = std::env::temp_dir().join(format!("replay-{}.ron", std::process::id()));
+    ReplayFile {
+        version: REPLAY_VERSION,
+        config: Some(empty_config()),
+        actions: Vec::new(),
+    }
+    .write(&path)
+    .unwrap();
+
+    let read = Replay::read(&path, &empty_config());
+    let other = Replay::read(&path, &GameConfig::default());
+    std::fs::remove_file(&path).unwrap();
+    assert!(read.is_ok());
+    assert!(other.is_err());
+}
+
+#[test]
+fn old_saves_still_load() {
+    let mut app = start_game(empty_config());
+    // a version 1 save: only woods, with a single slot and no stock, and enemy tiles without state
+    let path = std::env::temp_dir().join(format!("save-v1-{}.ron", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"(
+            version: 1,
+            cards: [
+                (
+                    info: (
+                        card_type: "villager",
+                        class: Villager,
+                        stats: (health: 2, max_health: 3, damage: 1),
+                    ),
+                    position: (0.0, 0.0),
+                    stack_parent: None,
+                    stack_child: None,
+                    slotted_in_tile: Some(0),
+                ),
+            ],
+            tiles: [
+                (location: (0, 0), kind: Woods(slotted_villager: Some(0)), progress: None),
+                (location: (0, 1), kind: Enemies, progress: None),
+            ],
+            stacks: [],
+        )"#,
+    )
+    .unwrap();
+    app.world
+        .resource_mut::<Events<LoadRequest>>()
+        .send(LoadRequest(path.clone()));
+    app.update();
+    app.update();
+    std::fs::remove_file(&path).unwrap();
+
+    let (villager, stats, slotted_in_tile) = app
+        .world
+        .query::<(Entity, &Card)>()
+        .iter(&app.world)
+        .map(|(entity, card)| (entity, card.info.stats.clone(), card.slotted_in_tile))
+        .next()
+        .unwrap();
+    assert_eq!(stats.health, 2);
+    assert!(stats.attack_speed > 0.0 && stats.work_speed > 0.0);
+    let woods = app.world.resource::<TileGrid>()[&IVec2::new(0, 0)];
+    assert_eq!(slotted_in_tile, Some(woods));
+    match *app.world.get::<Tile>(woods).unwrap() {
+        Tile::Producer {
+            kind,
+            slotted_villagers,
+            stock,
+            ..
+        } => {
+            assert_eq!(kind, TileKind::Woods);
+            assert_eq!(slotted_villagers[0], Some(villager));
+            assert_eq!(stock, TileKind::Woods.production().unwrap().stock);
+        }
+        tile => panic!("expected woods, got {:?}", tile.kind()),
+    }
+    let enemies = app.world.resource::<TileGrid>()[&IVec2::new(0, 1)];
+    assert_eq!(
+        app.world.get::<Tile>(enemies).unwrap().kind(),
+        TileKind::Enemies
+    );
+}
+
+#[test]
+fn loading_rewinds_the_clock() {
+    let mut app = start_game(empty_config());
+    let path = std::env::temp_dir().join(format!("save-{}.ron", std::process::id()));
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        world.resource::<SimulationClock>().tick() > 60
+    });
+    app.world
+        .resource_mut::<Events<SaveRequest>>()
+        .send(SaveRequest(path.clone()));
+    app.update();
+    let saved_tick = SaveGame::read(&path, app.world.resource::<CardRegistry>())
+        .unwrap()
+        .tick;
+    assert!(saved_tick > 60);
+
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        world.resource::<SimulationClock>().tick() > saved_tick + 600
+    });
+    app.world
+        .resource_mut::<Events<LoadRequest>>()
+        .send(LoadRequest(path.clone()));
+    app.update();
+    std::fs::remove_file(&path).unwrap();
+
+    // the frame that loaded may still have run a few steps at normal speed
+    let tick = app.world.resource::<SimulationClock>().tick();
+    assert!(
+        (saved_tick..saved_tick + 60).contains(&tick),
+        "loaded on tick {} from a save on tick {}",
+        tick,
+        saved_tick
+    );
+    assert_eq!(app.world.resource::<TimeScale>().0, 1.0);
+}
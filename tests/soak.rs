@@ -0,0 +1,108 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use bevy::prelude::*;
+use card_combinator::game::{
+    card::CardType,
+    config::{GameConfig, StartingStack},
+    progress_bar::{ProgressBar, ProgressBarPlugin, ProgressBarStatus},
+    tile::TileKind,
+    HeadlessPlugin,
+};
+
+use common::{count_cards, empty_config, run_until, start};
+
+/// A headless game that also draws progress bars, as far as that's possible without a GPU.
+fn app_with_bars(config: GameConfig) -> App {
+    let mut app = App::new();
+    app.add_plugin(HeadlessPlugin { config })
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .add_asset::<Font>()
+        .add_plugin(ProgressBarPlugin);
+    app
+}
+
+fn asset_counts(world: &World) -> (usize, usize) {
+    (
+        world.resource::<Assets<Mesh>>().len(),
+        world.resource::<Assets<StandardMaterial>>().len(),
+    )
+}
+
+/// Every breed spawns a recipe bar and despawns it once done. Bars share their meshes and
+/// materials, so the asset counts have to stay where they were before the first bar, no matter
+/// how many bars are up at once or how many have come and gone.
+#[test]
+fn progress_bars_keep_asset_counts_flat() {
+    const STACKS: usize = 10;
+    let mut app = app_with_bars(GameConfig {
+        stacks: (0..STACKS)
+            .map(|i| StartingStack {
+                cards: vec![CardType::VILLAGER, CardType::VILLAGER],
+                position: [i as f32 * 2.0, 0.0],
+            })
+            .collect(),
+        recipe_durations: [("breed".to_string(), 0.5)].into(),
+        ..empty_config()
+    });
+    let before_bars = asset_counts(&app.world);
+    start(&mut app);
+
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        world.query::<&ProgressBar>().iter(world).count() == STACKS
+    });
+    assert_eq!(asset_counts(&app.world), before_bars);
+
+    run_until(&mut app, Duration::from_secs(60), |world| {
+        count_cards(world, &CardType::VILLAGER) >= 100
+    });
+    assert_eq!(asset_counts(&app.world), before_bars);
+}
+
+/// How many progress bar fills were written to, summed over every frame.
+#[derive(Default)]
+struct RedrawnBars(usize);
+
+/// Progress bar fills that had their shape or color written to.
+type Redrawn = (
+    With<ProgressBarStatus>,
+    Or<(
+        Changed<Transform>,
+        Changed<Handle<Mesh>>,
+        Changed<Handle<StandardMaterial>>,
+    )>,
+);
+
+fn count_redrawn_bars(mut redrawn: ResMut<RedrawnBars>, statuses: Query<(), Redrawn>) {
+    redrawn.0 += statuses.iter().count();
+}
+
+/// Bars are only redrawn when they change. Nobody works the tiles of this board, so once their
+/// stock bars are drawn they should never be touched again.
+#[test]
+fn idle_progress_bars_are_not_redrawn() {
+    let mut app = app_with_bars(GameConfig {
+        tiles: GameConfig::default()
+            .tiles
+            .into_iter()
+            .filter(|tile| tile.kind != TileKind::Enemies)
+            .collect(),
+        ..empty_config()
+    });
+    app.init_resource::<RedrawnBars>()
+        .add_system_to_stage(CoreStage::Last, count_redrawn_bars);
+    start(&mut app);
+
+    run_until(&mut app, Duration::from_secs(30), |world| {
+        world.resource::<RedrawnBars>().0 > 0
+    });
+    app.world.resource_mut::<RedrawnBars>().0 = 0;
+    // a few seconds of game time
+    for _ in 0..60 {
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(app.world.resource::<RedrawnBars>().0, 0);
+}